@@ -0,0 +1,254 @@
+use std::{collections::HashMap, path::Path, sync::LazyLock};
+
+use crate::definition::{load_definition, parse_definition, unquote};
+
+/// Something the rune loader understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    /// A hex digit, 0 to 15.
+    Digit(u8),
+    /// Write zero words, N times.
+    Zero,
+    /// Repeat the last written word, N times.
+    Repeat,
+    /// Left align and write the accumulated digits.
+    Left,
+    /// Right align and write the accumulated digits.
+    Right,
+    /// Skip forward N words.
+    Skip,
+    /// Skip forward a word without touching the accumulated digits.
+    Advance,
+}
+
+impl Symbol {
+    pub const CONTROLS: [Symbol; 6] = [Symbol::Zero, Symbol::Repeat, Symbol::Left, Symbol::Right, Symbol::Skip, Symbol::Advance];
+
+    /// Name used for the symbol in alphabet definitions.
+    pub fn name(&self) -> String {
+        match self {
+            Symbol::Digit(d) => format!("{:x}", d),
+            Symbol::Zero => "zero".to_string(),
+            Symbol::Repeat => "repeat".to_string(),
+            Symbol::Left => "left".to_string(),
+            Symbol::Right => "right".to_string(),
+            Symbol::Skip => "skip".to_string(),
+            Symbol::Advance => "advance".to_string(),
+        }
+    }
+
+    /// Character used for the symbol in hex notation.
+    pub fn notation(&self) -> char {
+        match self {
+            Symbol::Digit(d) => char::from_digit(*d as u32, 16).unwrap(),
+            Symbol::Zero => 'z',
+            Symbol::Repeat => '*',
+            Symbol::Left => '<',
+            Symbol::Right => '>',
+            Symbol::Skip => '_',
+            Symbol::Advance => '^',
+        }
+    }
+
+    pub fn from_notation(c: char) -> Option<Symbol> {
+        match c {
+            '0'..='9' | 'a'..='f' | 'A'..='F' => Some(Symbol::Digit(c.to_digit(16).unwrap() as u8)),
+            'z' => Some(Symbol::Zero),
+            '*' => Some(Symbol::Repeat),
+            '<' => Some(Symbol::Left),
+            '>' => Some(Symbol::Right),
+            '_' => Some(Symbol::Skip),
+            '^' => Some(Symbol::Advance),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Symbol> {
+        (0..16).map(Symbol::Digit).chain(Symbol::CONTROLS).find(|symbol| symbol.name() == name)
+    }
+}
+
+/// The stream VM's own character set. Each line names a symbol, then the glyph
+/// written for it, then any other characters the loader also accepts for it.
+const RUNES: &str = "\
+# Elder Futhark runes, as read by the stream VM
+0 = ᚺ 0
+1 = ᚾ 1
+2 = ᛁ 2
+3 = ᛃ 3
+4 = ᛈ 4
+5 = ᛇ 5
+6 = ᛉ 6
+7 = ᛊ 7
+8 = ᛏ 8
+9 = ᛒ 9
+a = ᛖ a A u U
+b = ᛗ b B v V
+c = ᛚ c C w W
+d = ᛜ d D x X
+e = ᛞ e E y Y
+f = ᛟ f F z Z
+zero = ᚠ
+repeat = ᚱ
+left = ᚲ
+right = ×
+skip = ᚢ
+advance = ᚨ
+";
+
+/// Plain hex digits, which is all the loader reads outside of runes.
+const ASCII: &str = "\
+# Plain hex digits, for channels that mangle runes
+0 = 0
+1 = 1
+2 = 2
+3 = 3
+4 = 4
+5 = 5
+6 = 6
+7 = 7
+8 = 8
+9 = 9
+a = a A u U
+b = b B v V
+c = c C w W
+d = d D x X
+e = e E y Y
+f = f F z Z
+";
+
+static RUNE_ALPHABET: LazyLock<Alphabet> = LazyLock::new(|| Alphabet::parse(RUNES).unwrap());
+
+/// Maps loader symbols to the glyphs written for them and the characters read as them.
+#[derive(Debug, Clone)]
+pub struct Alphabet {
+    glyphs: HashMap<Symbol, char>,
+    symbols: HashMap<char, Symbol>,
+}
+
+impl Alphabet {
+    /// The stream VM's rune alphabet.
+    pub fn runes() -> &'static Alphabet {
+        &RUNE_ALPHABET
+    }
+
+    pub fn ascii() -> Alphabet {
+        Alphabet::parse(ASCII).unwrap()
+    }
+
+    /// Parses an alphabet definition, one `symbol = glyph [aliases...]` per line.
+    ///
+    /// Symbols are `0` to `f` and `zero`, `repeat`, `left`, `right`, `skip` and
+    /// `advance`. Every digit needs a glyph; a control without one is spelled
+    /// out in digits where possible. Blank lines and `#` comments are ignored,
+    /// and characters may be quoted.
+    pub fn parse(definition: &str) -> Result<Alphabet, String> {
+        let mut alphabet = Alphabet { glyphs: HashMap::new(), symbols: HashMap::new() };
+
+        parse_definition(definition, "symbol = glyph", |name, chars| {
+            let Some(symbol) = Symbol::from_name(name) else {
+                return Err(format!("unknown symbol `{name}`."));
+            };
+            if alphabet.glyphs.contains_key(&symbol) {
+                return Err(format!("`{name}` is defined twice."));
+            }
+
+            let mut chars = chars.split_whitespace().map(|token| {
+                let token = unquote(token);
+                let mut it = token.chars();
+                match (it.next(), it.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(format!("`{token}` is not a single character.")),
+                }
+            });
+            let Some(glyph) = chars.next() else {
+                return Err(format!("`{name}` has no glyph."));
+            };
+            let glyph = glyph?;
+            alphabet.glyphs.insert(symbol, glyph);
+
+            for c in std::iter::once(Ok(glyph)).chain(chars) {
+                let c = c?;
+                if let Some(other) = alphabet.symbols.insert(c, symbol)
+                    && other != symbol {
+                    return Err(format!("`{c}` is already used for `{}`.", other.name()));
+                }
+            }
+            Ok(())
+        })?;
+
+        for d in 0..16 {
+            if !alphabet.glyphs.contains_key(&Symbol::Digit(d)) {
+                return Err(format!("Digit `{:x}` has no glyph.", d));
+            }
+        }
+
+        Ok(alphabet)
+    }
+
+    pub fn load(path: &Path) -> Result<Alphabet, String> {
+        load_definition(path, Alphabet::parse)
+    }
+
+    /// Parses a command line alphabet: `runes`, `ascii` or a definition file.
+    pub fn from_arg(arg: &str) -> Result<Alphabet, String> {
+        match arg {
+            "runes" => Ok(Alphabet::runes().clone()),
+            "ascii" => Ok(Alphabet::ascii()),
+            path => Alphabet::load(Path::new(path)),
+        }
+    }
+
+    /// The glyph written for a symbol, if the alphabet has one.
+    pub fn encode(&self, symbol: Symbol) -> Option<char> {
+        self.glyphs.get(&symbol).copied()
+    }
+
+    /// The symbol a character is read as, if any.
+    pub fn decode(&self, c: char) -> Option<Symbol> {
+        self.symbols.get(&c).copied()
+    }
+
+    /// Rewrites text in this alphabet as runes, keeping characters it doesn't read as they are.
+    pub fn to_runes(&self, text: &str) -> String {
+        text.chars()
+            .map(|c| self.decode(c).and_then(|symbol| Alphabet::runes().encode(symbol)).unwrap_or(c))
+            .collect()
+    }
+
+    /// Converts hex notation into this alphabet's glyphs, or `None` if it uses
+    /// a symbol the alphabet can't write.
+    pub fn render(&self, notation: &str) -> Option<String> {
+        notation.chars().map(|c| Symbol::from_notation(c).and_then(|symbol| self.encode(symbol))).collect()
+    }
+}
+
+#[test]
+fn test_rune_alphabet() {
+    let runes = Alphabet::runes();
+    assert_eq!(runes.encode(Symbol::Digit(0xa)), Some('ᛖ'));
+    assert_eq!(runes.encode(Symbol::Right), Some('×'));
+    assert_eq!(runes.decode('ᛟ'), Some(Symbol::Digit(0xf)));
+    assert_eq!(runes.decode('z'), Some(Symbol::Digit(0xf)));
+    assert_eq!(runes.decode('ᚨ'), Some(Symbol::Advance));
+    assert_eq!(runes.decode('!'), None);
+    assert_eq!(runes.render("42>3z"), Some("ᛈᛁ×ᛃᚠ".to_string()));
+    assert_eq!(Alphabet::ascii().render("42>3z"), None);
+    assert_eq!(Alphabet::ascii().render("0042"), Some("0042".to_string()));
+}
+
+#[test]
+fn test_parse_alphabet() {
+    let digits: String = (0..16).map(|d| format!("{:x} = {}\n", d, (b'A' + d) as char)).collect();
+    let alphabet = Alphabet::parse(&(digits.clone() + "zero = \"~\" # tilde\n\nskip = !")).unwrap();
+    assert_eq!(alphabet.render("1f2z_"), Some("BPC~!".to_string()));
+    assert_eq!(alphabet.decode('~'), Some(Symbol::Zero));
+    assert_eq!(alphabet.render("1*"), None);
+    assert_eq!(alphabet.to_runes("BP~ !?"), "ᚾᛟᚠ ᚢ?");
+
+    assert_eq!(Alphabet::parse("0 = a").unwrap_err(), "Digit `1` has no glyph.");
+    assert_eq!(Alphabet::parse(&(digits.clone() + "jump = j")).unwrap_err(), "Line 17: unknown symbol `jump`.");
+    assert_eq!(Alphabet::parse(&(digits.clone() + "zero = A")).unwrap_err(), "Line 17: `A` is already used for `0`.");
+    assert_eq!(Alphabet::parse(&(digits.clone() + "zero = ab")).unwrap_err(), "Line 17: `ab` is not a single character.");
+    assert_eq!(Alphabet::parse(&(digits + "zero")).unwrap_err(), "Line 17: expected `symbol = glyph`.");
+}
@@ -0,0 +1,72 @@
+use crate::image::MemoryImage;
+
+/// Words per row of an exported array.
+const ROW_LEN: usize = 8;
+
+/// Languages an image can be exported to as source code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    C,
+    Rust,
+    Js,
+}
+
+/// Renders an image as an array declaration named `name`, eight words to a
+/// row, with unwritten words as zero. Rust constants get their name in upper case.
+/// C has no zero-length arrays, so an empty image is only a comment there.
+pub fn image_to_array(image: &MemoryImage, language: Language, name: &str, address_comments: bool) -> String {
+    let words = image.dense();
+    if words.is_empty() && language == Language::C {
+        return format!("// {} is empty.\n", name);
+    }
+    let mut out = match language {
+        Language::C => format!("const uint16_t {}[{}] = {{\n", name, words.len()),
+        Language::Rust => format!("pub const {}: [u16; {}] = [\n", name.to_uppercase(), words.len()),
+        Language::Js => format!("export const {} = new Uint16Array([\n", name),
+    };
+
+    for (i, row) in words.chunks(ROW_LEN).enumerate() {
+        let values: Vec<String> = row.iter().map(|word| format!("0x{:04x},", word.value())).collect();
+        out += &format!("    {}", values.join(" "));
+        if address_comments {
+            out += &format!(" // {:04x}", i * ROW_LEN);
+        }
+        out += "\n";
+    }
+
+    out += match language {
+        Language::C => "};\n",
+        Language::Rust => "];\n",
+        Language::Js => "]);\n",
+    };
+    out
+}
+
+#[test]
+fn test_image_to_array() {
+    use crate::word::Word;
+
+    let mut words = vec![Some(Word::new(0xdead)), None];
+    words.resize(9, Some(Word::new(0x42)));
+    let image = MemoryImage::from_words(&words);
+
+    assert_eq!(image_to_array(&image, Language::C, "image", false), concat!(
+        "const uint16_t image[9] = {\n",
+        "    0xdead, 0x0000, 0x0042, 0x0042, 0x0042, 0x0042, 0x0042, 0x0042,\n",
+        "    0x0042,\n",
+        "};\n",
+    ));
+    assert_eq!(image_to_array(&MemoryImage::from_words(&words[..3]), Language::Rust, "image", true), concat!(
+        "pub const IMAGE: [u16; 3] = [\n",
+        "    0xdead, 0x0000, 0x0042, // 0000\n",
+        "];\n",
+    ));
+    assert_eq!(image_to_array(&image, Language::Js, "image", true), concat!(
+        "export const image = new Uint16Array([\n",
+        "    0xdead, 0x0000, 0x0042, 0x0042, 0x0042, 0x0042, 0x0042, 0x0042, // 0000\n",
+        "    0x0042, // 0008\n",
+        "]);\n",
+    ));
+    assert_eq!(image_to_array(&MemoryImage::new(), Language::C, "empty", false), "// empty is empty.\n");
+    assert_eq!(image_to_array(&MemoryImage::new(), Language::Rust, "empty", false), "pub const EMPTY: [u16; 0] = [\n];\n");
+}
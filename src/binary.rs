@@ -0,0 +1,328 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{image::MemoryImage, word::Word};
+
+/// Word address the VM loads code to.
+pub const CODE_ADDRESS: u16 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionKind {
+    Memory,
+    Code,
+}
+
+impl SectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Memory => "memory",
+            SectionKind::Code => "code",
+        }
+    }
+}
+
+/// Byte order of words in raw binary data.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+/// What to do with a trailing odd byte.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Pad {
+    /// Refuse the input
+    Error,
+    /// Zero the high byte, keeping the odd byte as the low byte
+    High,
+    /// Zero the low byte, keeping the odd byte as the high byte
+    Low,
+}
+
+/// A run of bytes from a w2s file and the word address it loads to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub address: u16,
+    pub data: Vec<u8>,
+}
+
+pub struct BinaryFile {
+    pub header: Vec<u8>,
+    pub version: u8,
+    pub memory_start: usize,
+    pub code_start: usize,
+    pub memory: Vec<u8>,
+    pub code: Vec<u8>,
+    /// Program counter to start at, if the file sets one.
+    pub entry: Option<u16>,
+    pub sections: Vec<Section>,
+}
+
+impl BinaryFile {
+    /// Builds a version 1 file holding every written word of an image, with
+    /// memory below `CODE_ADDRESS` and code from it. Gaps are filled with zeros.
+    /// The only entry point these files can hold is the start of their code.
+    pub fn from_image(image: &MemoryImage) -> Result<BinaryFile, String> {
+        let words = image.to_words();
+        let split = words.len().min(CODE_ADDRESS as usize);
+        let (memory, code) = words.split_at(split);
+        let to_bytes = |words: &[Option<Word>]| -> Vec<u8> {
+            let len = words.iter().rposition(Option::is_some).map_or(0, |last| last + 1);
+            words[..len].iter().flat_map(|w| w.unwrap_or(Word::new(0)).value().to_be_bytes()).collect()
+        };
+
+        let mut file = BinaryFile {
+            header: Vec::new(),
+            version: 1,
+            memory_start: 0,
+            code_start: 0,
+            memory: to_bytes(memory),
+            code: to_bytes(code),
+            entry: None,
+            sections: Vec::new(),
+        };
+        if image.entry.is_some_and(|entry| entry != CODE_ADDRESS || file.code.is_empty()) {
+            return Err(format!("Version 1 files can only start at their code section at {CODE_ADDRESS:04x}."));
+        }
+        file.entry = image.entry;
+        if !file.memory.is_empty() {
+            file.memory_start = 7;
+            file.sections.push(Section { kind: SectionKind::Memory, address: 0, data: file.memory.clone() });
+        }
+        if !file.code.is_empty() {
+            file.code_start = 7 + file.memory.len();
+            file.sections.push(Section { kind: SectionKind::Code, address: CODE_ADDRESS, data: file.code.clone() });
+        }
+        Ok(file)
+    }
+
+    /// Serializes the file with a fresh version 1 header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = b"MWvm".to_vec();
+        out.push(self.version);
+        out.push(self.memory_start as u8);
+        out.push(self.code_start as u8);
+        out.extend(&self.memory);
+        out.extend(&self.code);
+        out
+    }
+
+    /// Lays every section out at its load address as a region of its kind,
+    /// leaving the rest unwritten.
+    pub fn image(&self) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        image.entry = self.entry;
+        for section in &self.sections {
+            // An odd byte at the end of a section keeps its place as a high byte.
+            let words = binary_to_words(section.data.clone(), Endian::Big, Pad::Low).unwrap_or_default();
+            for (i, word) in words.iter().enumerate() {
+                image.set(section.address.wrapping_add(i as u16), *word);
+            }
+            image.add_region(section.kind.name(), section.kind, section.address, words.len() as u16);
+        }
+        image
+    }
+}
+
+/// Parses a w2s file from wave2_assembler.
+///
+/// Versions 0 and 1 hold a memory section loaded at 0 and a code section
+/// loaded at `CODE_ADDRESS`, located by two single byte file offsets. Later
+/// versions are refused rather than guessed at.
+pub fn parse_binary_file(bytes: Vec<u8>) -> Result<BinaryFile, String> {
+    let mut file = BinaryFile {
+        header: Vec::new(),
+        version: 0,
+        memory: Vec::new(),
+        memory_start: 0,
+        code_start: 0,
+        code: Vec::new(),
+        entry: None,
+        sections: Vec::new(),
+    };
+
+    // Check header
+    if bytes.len() < 7 {
+        return Err("Malformed header, file too short.".to_string());
+    }
+
+    let magic = &bytes[0..4];
+    if magic != b"MWvm" {
+        return Err("Invalid magic number.".to_string());
+    }
+
+    let version = &bytes[4];
+    file.version = *version;
+
+    match version {
+        0 | 1 => {
+            let mem_start = bytes[5] as usize;
+            let code_start = bytes[6] as usize;
+
+            if mem_start.max(code_start) > bytes.len() {
+                return Err("Section starts past the end of the file.".to_string());
+            }
+            if mem_start != 0 && code_start != 0 && code_start < mem_start {
+                return Err("Code section starts before memory section.".to_string());
+            }
+
+            let mem_size = match (mem_start, code_start) {
+                (0, _) => 0,
+                (_, 0) => bytes.len() - mem_start,
+                (_, _) => code_start - mem_start
+            };
+            let code_size = match (mem_start, code_start) {
+                (_, 0) => 0,
+                (_, _) => bytes.len() - code_start
+            };
+
+            file.header = bytes[0..7].to_vec();
+
+            file.memory = bytes[mem_start..mem_start + mem_size].to_vec();
+            file.code = bytes[code_start..code_start + code_size].to_vec();
+            file.memory_start = mem_start;
+            file.code_start = code_start;
+
+            if mem_start != 0 {
+                file.sections.push(Section { kind: SectionKind::Memory, address: 0, data: file.memory.clone() });
+            }
+            if code_start != 0 {
+                file.sections.push(Section { kind: SectionKind::Code, address: CODE_ADDRESS, data: file.code.clone() });
+                // The VM starts running from the code section.
+                file.entry = Some(CODE_ADDRESS);
+            }
+        },
+        _ => return Err(format!("Unsupported version {version}, only versions 0 and 1 are supported.")),
+    }
+
+    Ok(file)
+}
+
+#[test]
+fn test_bin_file_loader() {
+    let bytes = b"MWvm\x01\x00\x00";
+    let file = parse_binary_file(bytes.to_vec()).unwrap();
+    assert_eq!(file.header, b"MWvm\x01\x00\x00");
+    assert_eq!(file.memory, b"");
+    assert_eq!(file.code, b"");
+    assert_eq!(file.entry, None);
+
+    let bytes = b"MWvm\x01\x07\x08\x01\x02";
+    let file = parse_binary_file(bytes.to_vec()).unwrap();
+    assert_eq!(file.header, b"MWvm\x01\x07\x08");
+    assert_eq!(file.memory, b"\x01");
+    assert_eq!(file.code, b"\x02");
+    assert_eq!(file.image().entry, Some(CODE_ADDRESS));
+
+    let bytes = b"MWvm\x01\x07\x0b12345678";
+    let file = parse_binary_file(bytes.to_vec()).unwrap();
+    assert_eq!(file.header, b"MWvm\x01\x07\x0b");
+    assert_eq!(file.memory, b"1234");
+    assert_eq!(file.code, b"5678");
+
+    assert!(parse_binary_file(b"MWvm\x01\x20\x00".to_vec()).is_err());
+    assert!(parse_binary_file(b"MWvm\x01\x08\x07\x00".to_vec()).is_err());
+}
+
+#[test]
+fn test_bin_file_loader_v2() {
+    assert_eq!(
+        parse_binary_file(b"MWvm\x02\x00\x40\x00".to_vec()).err(),
+        Some("Unsupported version 2, only versions 0 and 1 are supported.".to_string()),
+    );
+}
+
+#[test]
+fn test_bin_file_writer() {
+    let words = |words: Vec<Option<u16>>| words.into_iter().map(|w| w.map(Word::new)).collect::<Vec<_>>();
+
+    let mut words_v1 = words(vec![Some(0xdead), Some(0xbeef)]);
+    words_v1.resize(0x40, None);
+    words_v1.push(Some(Word::new(0xcafe)));
+    let file = BinaryFile::from_image(&MemoryImage::from_words(&words_v1)).unwrap();
+    assert_eq!(file.to_bytes(), b"MWvm\x01\x07\x0b\xde\xad\xbe\xef\xca\xfe");
+    assert_eq!(parse_binary_file(file.to_bytes()).unwrap().image().to_words(), words_v1);
+
+    // Files with code start there, so writing one back out keeps its entry point.
+    let mut image = parse_binary_file(file.to_bytes()).unwrap().image();
+    assert_eq!(BinaryFile::from_image(&image).unwrap().to_bytes(), file.to_bytes());
+    image.entry = Some(0x41);
+    assert_eq!(BinaryFile::from_image(&image).err(), Some("Version 1 files can only start at their code section at 0040.".to_string()));
+
+    let file = BinaryFile::from_image(&MemoryImage::from_words(&words(vec![Some(0x1234)]))).unwrap();
+    assert_eq!(file.to_bytes(), b"MWvm\x01\x07\x00\x12\x34");
+
+    // Gaps are filled with zeros.
+    let mut image = MemoryImage::from_words(&words(vec![Some(0x1234), None, Some(0x0000)]));
+    let file = BinaryFile::from_image(&image).unwrap();
+    assert_eq!(file.to_bytes(), b"MWvm\x01\x07\x00\x12\x34\x00\x00\x00\x00");
+
+    image.entry = Some(CODE_ADDRESS);
+    assert_eq!(BinaryFile::from_image(&image).err(), Some("Version 1 files can only start at their code section at 0040.".to_string()));
+}
+
+/// Pairs bytes up into words in the given byte order, padding out a trailing odd byte as `pad` says.
+pub fn binary_to_words(input: Vec<u8>, endian: Endian, pad: Pad) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+
+    let chunks = input.chunks_exact(2);
+    let odd = chunks.remainder().first().copied();
+    for chunk in chunks {
+        let bytes = [chunk[0], chunk[1]];
+        let word = match endian {
+            Endian::Big => u16::from_be_bytes(bytes),
+            Endian::Little => u16::from_le_bytes(bytes),
+        };
+        words.push(Word::new(word));
+    }
+
+    if let Some(byte) = odd {
+        words.push(Word::new(match pad {
+            Pad::Error => return Err(format!("Input is {} bytes long, which is not a whole number of words.", input.len())),
+            Pad::High => byte as u16,
+            Pad::Low => (byte as u16) << 8,
+        }));
+    }
+
+    Ok(words)
+}
+
+#[test]
+fn test_binary_to_words() {
+    let input = vec![0x12, 0x34, 0x56, 0x78];
+    let words = binary_to_words(input, Endian::Big, Pad::Error).unwrap();
+    assert_eq!(words.len(), 2);
+    assert_eq!(words[0].value(), 0x1234);
+    assert_eq!(words[1].value(), 0x5678);
+
+    let words = binary_to_words(vec![0x12, 0x34, 0x56], Endian::Little, Pad::High).unwrap();
+    assert_eq!(words, vec![Word::new(0x3412), Word::new(0x0056)]);
+    let words = binary_to_words(vec![0x56], Endian::Little, Pad::Low).unwrap();
+    assert_eq!(words, vec![Word::new(0x5600)]);
+    assert_eq!(
+        binary_to_words(vec![0x12, 0x34, 0x56], Endian::Big, Pad::Error).unwrap_err(),
+        "Input is 3 bytes long, which is not a whole number of words."
+    );
+}
+
+/// Lays an image out as bytes in the given byte order, with unwritten words as zero.
+pub fn image_to_binary(image: &MemoryImage, endian: Endian) -> Vec<u8> {
+    image.dense().iter()
+        .flat_map(|word| {
+            let value = word.value();
+            match endian {
+                Endian::Big => value.to_be_bytes(),
+                Endian::Little => value.to_le_bytes(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_image_to_binary() {
+    let image = MemoryImage::from_words(&[Some(Word::new(0x1234)), None, Some(Word::new(0xbeef))]);
+    assert_eq!(image_to_binary(&image, Endian::Big), vec![0x12, 0x34, 0, 0, 0xbe, 0xef]);
+    assert_eq!(image_to_binary(&image, Endian::Little), vec![0x34, 0x12, 0, 0, 0xef, 0xbe]);
+}
@@ -0,0 +1,271 @@
+use std::ops::Range;
+
+use crate::alphabet::{Alphabet, Symbol};
+
+/// What a group of runes does to memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Four digits filled the accumulator and were written as a word.
+    Write(u16),
+    /// `×` wrote the accumulated digits right aligned.
+    WriteRight(u16),
+    /// `ᚲ` wrote the accumulated digits left aligned.
+    WriteLeft(u16),
+    /// Digits left over at the end of input, written without advancing.
+    Partial(u16),
+    /// `ᚠ` wrote `count` zero words.
+    ZeroFill(u16),
+    /// `ᚱ` wrote the last written value `count` times.
+    Repeat(u16, u16),
+    /// `ᚢ` or `ᚨ` moved the address forward without writing.
+    Skip(u16),
+    /// A character the loader doesn't understand, which it ignores.
+    Unknown(char),
+}
+
+/// A single decoded group, with the address it starts at and the span of
+/// characters (not bytes) in the input it was decoded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub action: Action,
+    pub address: u16,
+    pub span: Range<usize>,
+}
+
+impl Event {
+    /// The `(address, value)` pairs this event writes, in order.
+    pub fn writes(&self) -> Vec<(u16, u16)> {
+        let (value, count) = match self.action {
+            Action::Write(value) | Action::WriteRight(value) | Action::WriteLeft(value) | Action::Partial(value) => (value, 1),
+            Action::ZeroFill(count) => (0, count),
+            Action::Repeat(value, count) => (value, count),
+            Action::Skip(_) | Action::Unknown(_) => return Vec::new(),
+        };
+        let mut addr = self.address;
+        let mut out = Vec::new();
+        for _ in 0..count {
+            out.push((addr, value));
+            addr = addr.wrapping_add(1) % 0x1000;
+        }
+        out
+    }
+}
+
+/// Decodes a rune string one group at a time, following the VM rune loader.
+pub struct Decoder {
+    alphabet: Alphabet,
+    chars: Vec<char>,
+    pos: usize,
+    val: u16,
+    ofs_index: usize,
+    addr: u16,
+    last_written: u16,
+    group_start: Option<usize>,
+}
+
+const ORDER: [u32; 4] = [12, 8, 4, 0];
+const UNORDER: [u32; 5] = [0, 12, 8, 4, 0];
+
+impl Decoder {
+    pub fn new(runes: &str) -> Self {
+        Decoder::with_alphabet(runes, Alphabet::runes())
+    }
+
+    /// Decodes characters from another alphabet, as a loader reading it would.
+    pub fn with_alphabet(runes: &str, alphabet: &Alphabet) -> Self {
+        Decoder {
+            alphabet: alphabet.clone(),
+            chars: runes.chars().collect(),
+            pos: 0,
+            val: 0,
+            ofs_index: 0,
+            addr: 0,
+            last_written: 0,
+            group_start: None,
+        }
+    }
+
+    /// Address the next write will land on.
+    #[allow(unused)]
+    pub fn address(&self) -> u16 {
+        self.addr
+    }
+
+    /// Value a `ᚱ` would currently repeat.
+    #[allow(unused)]
+    pub fn last_written(&self) -> u16 {
+        self.last_written
+    }
+
+    /// Right aligns the accumulated digits and takes them as a count, where no digits counts as one.
+    fn take_count(&mut self) -> u16 {
+        let count = self.val >> UNORDER[self.ofs_index];
+        self.ofs_index = 0;
+        self.val = 0;
+        if count == 0 { 1 } else { count }
+    }
+
+    /// Moves the address forward, wrapping at the end of memory as the VM does.
+    fn advance(&mut self, count: u16) {
+        self.addr = self.addr.wrapping_add(count) % 0x1000;
+    }
+
+    fn event(&mut self, action: Action, address: u16) -> Event {
+        let start = self.group_start.take().unwrap_or(self.pos);
+        Event { action, address, span: start..self.pos + 1 }
+    }
+
+    fn step(&mut self, c: char) -> Option<Event> {
+        let address = self.addr;
+        let action = match self.alphabet.decode(c) {
+            // accumulate a digit, writing the word once all four are in
+            Some(Symbol::Digit(hex_val)) => {
+                self.group_start.get_or_insert(self.pos);
+                self.val |= (hex_val as u16) << ORDER[self.ofs_index];
+                self.ofs_index += 1;
+                if self.ofs_index < 4 {
+                    return None;
+                }
+                self.last_written = self.val;
+                self.val = 0;
+                self.advance(1);
+                self.ofs_index = 0;
+                Action::Write(self.last_written)
+            }
+            // skip forward in the address space by N
+            Some(Symbol::Skip) => {
+                let count = self.take_count();
+                self.advance(count);
+                Action::Skip(count)
+            }
+            // skip forward a word without writing
+            // and without affecting input
+            Some(Symbol::Advance) => {
+                self.advance(1);
+                return Some(Event { action: Action::Skip(1), address, span: self.pos..self.pos + 1 });
+            }
+            // write 0 words, N times
+            Some(Symbol::Zero) => {
+                let count = self.take_count();
+                self.advance(count);
+                Action::ZeroFill(count)
+            }
+            // repeat the "last written" value 1 or N times
+            Some(Symbol::Repeat) => {
+                let count = self.take_count();
+                self.advance(count);
+                Action::Repeat(self.last_written, count)
+            }
+            // right align and write current value
+            Some(Symbol::Right) => {
+                self.last_written = self.val >> UNORDER[self.ofs_index];
+                self.advance(1);
+                self.ofs_index = 0;
+                self.val = 0;
+                Action::WriteRight(self.last_written)
+            }
+            // left align and write current value
+            Some(Symbol::Left) => {
+                self.last_written = self.val;
+                self.advance(1);
+                self.ofs_index = 0;
+                self.val = 0;
+                Action::WriteLeft(self.last_written)
+            }
+            None if c.is_whitespace() => return None,
+            None => return Some(Event { action: Action::Unknown(c), address, span: self.pos..self.pos + 1 }),
+        };
+        Some(self.event(action, address))
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        while self.pos < self.chars.len() {
+            let c = self.chars[self.pos];
+            let event = self.step(c);
+            self.pos += 1;
+            if event.is_some() {
+                return event;
+            }
+        }
+        if self.ofs_index > 0 {
+            // Leftover digits are written as they are, left aligned.
+            let value = self.val;
+            self.ofs_index = 0;
+            self.val = 0;
+            let start = self.group_start.take().unwrap_or(self.pos);
+            return Some(Event { action: Action::Partial(value), address: self.addr, span: start..self.pos });
+        }
+        None
+    }
+}
+
+#[test]
+fn test_decoder_events() {
+    let events: Vec<Event> = Decoder::new("ᛜᛞᛖᛜ ᛃᚱᚠᛈᛁ×ᛇᚲᛁᚢᚨᛇᛟ?").collect();
+    assert_eq!(events, vec![
+        Event { action: Action::Write(0xdead), address: 0, span: 0..4 },
+        Event { action: Action::Repeat(0xdead, 3), address: 1, span: 5..7 },
+        Event { action: Action::ZeroFill(1), address: 4, span: 7..8 },
+        Event { action: Action::WriteRight(0x42), address: 5, span: 8..11 },
+        Event { action: Action::WriteLeft(0x5000), address: 6, span: 11..13 },
+        Event { action: Action::Skip(2), address: 7, span: 13..15 },
+        Event { action: Action::Skip(1), address: 9, span: 15..16 },
+        Event { action: Action::Unknown('?'), address: 10, span: 18..19 },
+        Event { action: Action::Partial(0x5f00), address: 10, span: 16..19 },
+    ]);
+}
+
+#[test]
+fn test_event_writes() {
+    let event = Event { action: Action::Repeat(0xbeef, 3), address: 0xffe, span: 0..2 };
+    assert_eq!(event.writes(), vec![(0xffe, 0xbeef), (0xfff, 0xbeef), (0x000, 0xbeef)]);
+    let event = Event { action: Action::Skip(3), address: 0, span: 0..2 };
+    assert_eq!(event.writes(), vec![]);
+}
+
+#[test]
+fn test_loader_conformance() {
+    // Runes, the (address, value) writes the VM loader makes, and the address it ends on.
+    type Case = (&'static str, &'static [(u16, u16)], u16);
+    let cases: &[Case] = &[
+        ("ᛜᛞᛖᛜ", &[(0, 0xdead)], 1),
+        ("dead BEEF", &[(0, 0xdead), (1, 0xbeef)], 2),
+        ("ᛜᛞ\nᛖ ᛜ", &[(0, 0xdead)], 1),
+        // Characters the loader doesn't know are ignored, even mid-word.
+        ("ᛜᛞ?ᛖᛜ", &[(0, 0xdead)], 1),
+        // `ᚨ` moves on a word without touching the accumulated digits.
+        ("ᛜᛞᚨᛖᛜ", &[(1, 0xdead)], 2),
+        // Counts of zero, or no digits at all, mean one.
+        ("ᚠ", &[(0, 0)], 1),
+        ("ᚺᚠ", &[(0, 0)], 1),
+        ("ᚺᚢᚾ×", &[(1, 1)], 2),
+        ("ᛃᚠ", &[(0, 0), (1, 0), (2, 0)], 3),
+        // `ᚱ` repeats the last value written, zero before anything is.
+        ("ᚱ", &[(0, 0)], 1),
+        ("ᛁ×ᛁᚱ", &[(0, 2), (1, 2), (2, 2)], 3),
+        ("ᛁᚲᚱ", &[(0, 0x2000), (1, 0x2000)], 2),
+        ("ᛁᛃᛈᛇᚠᚱ", &[(0, 0x2345), (1, 0), (2, 0x2345)], 3),
+        // `×` and `ᚲ` with no digits write zero.
+        ("×ᚲ", &[(0, 0), (1, 0)], 2),
+        // Digits left at the end of input are written left aligned without moving on.
+        ("ᛁᛃ", &[(0, 0x2300)], 0),
+        ("ᚾᚾᚾᚾᛁ", &[(0, 0x1111), (1, 0x2000)], 1),
+        // Every way of moving the address wraps at the end of memory.
+        ("ᛟᛟᛟᚢᚾᛁᛃᛈᛇᛇᛇᛇ", &[(0xfff, 0x1234), (0, 0x5555)], 1),
+        ("ᛟᛟᛟᚢᚨᚨ", &[], 1),
+        ("ᛟᛟᛞᚢᛃᚠ", &[(0xffe, 0), (0xfff, 0), (0, 0)], 1),
+        ("ᛟᛟᛟᚢᛁ×ᛁᚱ", &[(0xfff, 2), (0, 2), (1, 2)], 2),
+        ("ᛟᛟᛟᚢᛁᚲ", &[(0xfff, 0x2000)], 0),
+        ("ᛟᛟᛟᚢᛁᚢ", &[], 1),
+    ];
+
+    for (runes, writes, end) in cases {
+        let mut decoder = Decoder::new(runes);
+        let actual: Vec<(u16, u16)> = decoder.by_ref().flat_map(|event| event.writes()).collect();
+        assert_eq!((actual.as_slice(), decoder.address()), (*writes, *end), "{runes:?}");
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+/// Reads a definition, one `name = value` per line, handing each name and
+/// value to `define` with their whitespace trimmed. Blank lines and `#`
+/// comments are ignored, and a name can only be defined once.
+///
+/// `expected` describes a line for the error when one isn't a definition,
+/// and errors from `define` are prefixed with the line they came from.
+pub fn parse_definition(definition: &str, expected: &str, mut define: impl FnMut(&str, &str) -> Result<(), String>) -> Result<(), String> {
+    let mut defined = Vec::new();
+
+    for (i, line) in definition.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(format!("Line {line_no}: expected `{expected}`."));
+        };
+        let name = name.trim();
+        if defined.contains(&name) {
+            return Err(format!("Line {line_no}: `{name}` is defined twice."));
+        }
+        defined.push(name);
+        define(name, value.trim()).map_err(|err| format!("Line {line_no}: {err}"))?;
+    }
+
+    Ok(())
+}
+
+/// Strips the quotes a value may be written in.
+pub fn unquote(value: &str) -> &str {
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Reads a definition file and parses it with `parse`, naming the file in errors.
+pub fn load_definition<T>(path: &Path, parse: impl FnOnce(&str) -> Result<T, String>) -> Result<T, String> {
+    let definition = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    parse(&definition).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+#[test]
+fn test_parse_definition() {
+    let mut definitions = Vec::new();
+    let definition = "# header\n\na = 1 # one\n  b=\"two words\"  \nc =\n";
+    parse_definition(definition, "name = value", |name, value| {
+        definitions.push((name.to_string(), unquote(value).to_string()));
+        Ok(())
+    }).unwrap();
+    assert_eq!(definitions, [("a", "1"), ("b", "two words"), ("c", "")].map(|(name, value)| (name.to_string(), value.to_string())));
+
+    let ok = |_: &str, _: &str| Ok(());
+    assert_eq!(parse_definition("a = 1\n\nb", "name = value", ok).unwrap_err(), "Line 3: expected `name = value`.");
+    assert_eq!(parse_definition("a = 1\na = 2", "name = value", ok).unwrap_err(), "Line 2: `a` is defined twice.");
+    assert_eq!(parse_definition("# a\na = 1", "name = value", |name, _| Err(format!("bad `{name}`."))).unwrap_err(), "Line 2: bad `a`.");
+}
@@ -1,28 +1,25 @@
+use crate::{image::MemoryImage, word::Word};
+
+/// Runs of words to write over `a` to turn it into `b`, as `(address, words)`.
+/// Words `a` writes that `b` doesn't are cleared to zero.
 #[allow(unused)]
-pub fn diff(a: Vec<u8>, b: Vec<u8>) -> Vec<(usize, Vec<u8>)> {
-    let mut c = Vec::new();
-    let diffs = Vec::new();
-
-    c.resize(a.len().max(b.len()), None);
-
-    let min = a.len().min(b.len());
-    let max = a.len().max(b.len());
-    for i in 0..min {
-        c[i] = match a[i] == b[i] {
-            true => Some(b[i]),
-            false => None,
-        };
-    }
-    for i in min..max {
-        c[i] = match a.len() > b.len() {
-            true => Some(0),
-            false => Some(b[i]),
+pub fn diff(a: &MemoryImage, b: &MemoryImage) -> Vec<(u16, Vec<Word>)> {
+    let mut diffs: Vec<(u16, Vec<Word>)> = Vec::new();
+
+    let max = a.len().max(b.len()) as u16;
+    for i in 0..max {
+        let value = match (a.get(i), b.get(i)) {
+            (_, Some(value)) => value,
+            (Some(_), None) => Word::new(0),
+            (None, None) => continue,
         };
-    }
-
-    let i = 0;
-    while i < max {
-
+        if a.get(i) == Some(value) {
+            continue;
+        }
+        match diffs.last_mut() {
+            Some((start, run)) if *start as usize + run.len() == i as usize => run.push(value),
+            _ => diffs.push((i, vec![value])),
+        }
     }
 
     diffs
@@ -30,18 +27,26 @@ pub fn diff(a: Vec<u8>, b: Vec<u8>) -> Vec<(usize, Vec<u8>)> {
 
 #[test]
 fn test_diff() {
-    let a = vec![1, 2, 3, 4, 5];
-    let b = vec![1, 2, 6, 7, 8];
-    let expected = vec![(2, vec![6, 7, 8])];
-    assert_eq!(diff(a.clone(), b.clone()), expected);
-
-    let a = vec![1, 2, 3, 4, 5, 6, 7,  8, 9, 10, 15, 16, 17, 18];
-    let b = vec![1, 2, 3, 5, 6, 7, 15, 8, 9, 10, 11, 12, 17, 18, 19, 20, 22];
-    let expected = vec![(3, vec![5, 6, 7, 15]), (10, vec![11, 12]), (14, vec![19, 20, 22])];
-    assert_eq!(diff(a.clone(), b.clone()), expected);
-
-    let a = vec![1, 2, 3, 4, 5];
-    let b = vec![1, 2, 3];
-    let expected = vec![(3, vec![0, 0])];
-    assert_eq!(diff(a.clone(), b.clone()), expected);
+    let image = |values: Vec<u16>| MemoryImage::from_words(&values.into_iter().map(|v| Some(Word::new(v))).collect::<Vec<_>>());
+    let words = |values: Vec<u16>| values.into_iter().map(Word::new).collect::<Vec<_>>();
+
+    let a = image(vec![1, 2, 3, 4, 5]);
+    let b = image(vec![1, 2, 6, 7, 8]);
+    let expected = vec![(2, words(vec![6, 7, 8]))];
+    assert_eq!(diff(&a, &b), expected);
+
+    let a = image(vec![1, 2, 3, 4, 5, 6, 7,  8, 9, 10, 15, 16, 17, 18]);
+    let b = image(vec![1, 2, 3, 5, 6, 7, 15, 8, 9, 10, 11, 12, 17, 18, 19, 20, 22]);
+    let expected = vec![(3, words(vec![5, 6, 7, 15])), (10, words(vec![11, 12])), (14, words(vec![19, 20, 22]))];
+    assert_eq!(diff(&a, &b), expected);
+
+    let a = image(vec![1, 2, 3, 4, 5]);
+    let b = image(vec![1, 2, 3]);
+    let expected = vec![(3, words(vec![0, 0]))];
+    assert_eq!(diff(&a, &b), expected);
+
+    // Gaps in both images are left alone.
+    let a = MemoryImage::from_words(&[Some(Word::new(1)), None, Some(Word::new(3))]);
+    let b = MemoryImage::from_words(&[None, None, Some(Word::new(4))]);
+    assert_eq!(diff(&a, &b), vec![(0, words(vec![0])), (2, words(vec![4]))]);
 }
@@ -0,0 +1,83 @@
+use crate::{alphabet::Alphabet, decoder::{Action, Decoder, Event}};
+
+/// Lists every group in a rune string on its own line, with its ASCII
+/// equivalent, the addresses it touches and the words it writes.
+///
+/// Text in another alphabet is read as runes first, like `-r` and `-d` do,
+/// and listed as it was written.
+pub fn explain(runes: &str, alphabet: &Alphabet) -> String {
+    let chars: Vec<char> = runes.chars().collect();
+    let runes = alphabet.to_runes(runes);
+    let rune_chars: Vec<char> = runes.chars().collect();
+    let rows: Vec<[String; 4]> = Decoder::new(&runes)
+        .map(|event| {
+            let group: String = chars[event.span.clone()].iter().filter(|c| !c.is_whitespace()).collect();
+            let ascii: String = rune_chars[event.span.clone()].iter()
+                .filter(|c| !c.is_whitespace())
+                .map(|&c| Alphabet::runes().decode(c).map_or(c, |symbol| symbol.notation()))
+                .collect();
+            [group, ascii, address_range(&event), describe(&event)]
+        })
+        .collect();
+
+    let mut widths = [5, 5, 9];
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let mut out = format!("{:<w0$}  {:<w1$}  {:<w2$}  words", "runes", "ascii", "address", w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+    for [group, ascii, range, words] in rows {
+        out += &format!("\n{:<w0$}  {:<w1$}  {:<w2$}  {}", group, ascii, range, words, w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+    }
+    out
+}
+
+/// The first and last address an event touches, wrapping at the end of memory.
+fn address_range(event: &Event) -> String {
+    let count = match event.action {
+        Action::ZeroFill(count) | Action::Repeat(_, count) | Action::Skip(count) => count,
+        Action::Unknown(_) => return String::new(),
+        _ => 1,
+    };
+    let last = event.address.wrapping_add(count - 1) % 0x1000;
+    if count == 1 {
+        format!("{:04x}", event.address)
+    } else {
+        format!("{:04x}-{:04x}", event.address, last)
+    }
+}
+
+fn describe(event: &Event) -> String {
+    match event.action {
+        Action::Write(value) | Action::WriteRight(value) | Action::WriteLeft(value) => format!("{:04x}", value),
+        Action::Partial(value) => format!("{:04x} (partial word at end of input)", value),
+        Action::ZeroFill(1) => "0000".to_string(),
+        Action::ZeroFill(count) => format!("0000 x{:x}", count),
+        Action::Repeat(value, 1) => format!("{:04x}", value),
+        Action::Repeat(value, count) => format!("{:04x} x{:x}", value, count),
+        Action::Skip(_) => "skipped".to_string(),
+        Action::Unknown(_) => "ignored".to_string(),
+    }
+}
+
+#[test]
+fn test_explain() {
+    assert_eq!(explain("ᛜᛞᛖᛜ ᛃᚱᚠᛈᛁ×ᛁᚢ?", Alphabet::runes()), [
+        "runes  ascii  address    words",
+        "ᛜᛞᛖᛜ   dead   0000       dead",
+        "ᛃᚱ     3*     0001-0003  dead x3",
+        "ᚠ      z      0004       0000",
+        "ᛈᛁ×    42>    0005       0042",
+        "ᛁᚢ     2_     0006-0007  skipped",
+        "?      ?                 ignored",
+    ].join("\n"));
+
+    // Runes still read as runes when another alphabet is chosen.
+    assert_eq!(explain("ᛜᛞᛖᛜ", &Alphabet::ascii()), explain("ᛜᛞᛖᛜ", Alphabet::runes()));
+    assert_eq!(explain("dead", &Alphabet::ascii()), [
+        "runes  ascii  address    words",
+        "dead   dead   0000       dead",
+    ].join("\n"));
+}
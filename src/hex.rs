@@ -0,0 +1,162 @@
+use crate::{binary::{image_to_binary, Endian}, image::{MemoryImage, MEMORY_SIZE}, word::Word};
+
+/// Reads a hex listing into an image.
+///
+/// Words are groups of four hex digits, separated by whitespace or run
+/// together, or pairs of two digit bytes, high byte first. A `0x` prefix marks a single word of up to four digits, and
+/// `----` leaves a word unwritten. A line may start with an `address:` to
+/// continue from, leaving anything skipped over unwritten. `;`, `#` and `//`
+/// start comments.
+pub fn hex_to_image(hex_string: String) -> Result<MemoryImage, String> {
+    let mut image = MemoryImage::new();
+    let mut addr = 0;
+
+    for (i, line) in hex_string.lines().enumerate() {
+        let line_no = i + 1;
+        let line = [";", "#", "//"].iter().fold(line, |line, marker| line.split_once(marker).map_or(line, |(line, _)| line));
+
+        let mut tokens = Vec::new();
+        let mut column = 0;
+        for (c_no, c) in line.chars().enumerate() {
+            if c.is_whitespace() {
+                column = 0;
+            } else if column == 0 {
+                column = c_no + 1;
+                tokens.push((column, c.to_string()));
+            } else {
+                tokens.last_mut().unwrap().1.push(c);
+            }
+        }
+
+        // Runs of two digit bytes, like `xxd -g1` prints, pair up into words.
+        let is_byte = |token: &str| token.len() == 2 && token.chars().all(|c| c.is_ascii_hexdigit());
+        let mut words: Vec<(usize, String)> = Vec::new();
+        let mut run = 0;
+        for (col, token) in tokens {
+            if is_byte(&token) && run % 2 == 1 {
+                words.last_mut().unwrap().1.push_str(&token);
+            } else {
+                words.push((col, token.clone()));
+            }
+            run = if is_byte(&token) { run + 1 } else { 0 };
+        }
+
+        for (n, (col, token)) in words.into_iter().enumerate() {
+            let error = |msg: &str| Err(format!("Line {line_no}, column {col}: {msg}"));
+            let parse = |digits: &str| u16::from_str_radix(digits, 16).ok().filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()));
+
+            if let Some(address) = token.strip_suffix(':') {
+                if n > 0 {
+                    return error("an address can only start a line.");
+                }
+                let address = address.strip_prefix("0x").unwrap_or(address);
+                match parse(address).filter(|a| (*a as usize) < MEMORY_SIZE) {
+                    Some(address) => addr = address as usize,
+                    None => return error(&format!("`{address}` is not an address in memory.")),
+                }
+                continue;
+            }
+
+            let values = if token == "----" {
+                vec![None]
+            } else if let Some(digits) = token.strip_prefix("0x") {
+                match parse(digits).filter(|_| digits.len() <= 4) {
+                    Some(value) => vec![Some(Word::new(value))],
+                    None => return error(&format!("`{token}` is not a hex word.")),
+                }
+            } else if token.len() % 4 == 0 && token.is_ascii() {
+                let mut values = Vec::new();
+                for chunk in token.as_bytes().chunks(4) {
+                    match std::str::from_utf8(chunk).ok().and_then(parse) {
+                        Some(value) => values.push(Some(Word::new(value))),
+                        None => return error(&format!("`{token}` is not made of hex words.")),
+                    }
+                }
+                values
+            } else {
+                return error(&format!("`{token}` is not made of four digit hex words."));
+            };
+
+            for value in values {
+                if addr >= MEMORY_SIZE {
+                    return error("word runs past the end of memory.");
+                }
+                if let Some(value) = value {
+                    image.set(addr as u16, value);
+                }
+                addr += 1;
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+#[test]
+fn test_hex_to_image() {
+    let hex_string = "12345678".to_string();
+    let words = hex_to_image(hex_string).unwrap().to_words();
+    assert_eq!(words.len(), 2);
+    assert_eq!(words[0], Some(Word::new(0x1234)));
+    assert_eq!(words[1], Some(Word::new(0x5678)));
+
+    let words = hex_to_image("1234 ---- 0000".to_string()).unwrap().to_words();
+    assert_eq!(words, vec![Some(Word::new(0x1234)), None, Some(Word::new(0))]);
+
+    let listing = "; header\n0000: dead beef  ; entry\n\n0x0004: 0x42 cafe # two\n// done\n";
+    let words = hex_to_image(listing.to_string()).unwrap().to_words();
+    assert_eq!(words, vec![
+        Some(Word::new(0xdead)), Some(Word::new(0xbeef)), None, None,
+        Some(Word::new(0x0042)), Some(Word::new(0xcafe)),
+    ]);
+
+    let words = hex_to_image("0000: de ad be ef 1234\n0003: 00 42".to_string()).unwrap().to_words();
+    assert_eq!(words, vec![Some(Word::new(0xdead)), Some(Word::new(0xbeef)), Some(Word::new(0x1234)), Some(Word::new(0x0042))]);
+
+    assert_eq!(hex_to_image("de ad be".to_string()).unwrap_err(), "Line 1, column 7: `be` is not made of four digit hex words.");
+    assert_eq!(hex_to_image("0000: dead bee".to_string()).unwrap_err(), "Line 1, column 12: `bee` is not made of four digit hex words.");
+    assert_eq!(hex_to_image("\n dead 0x12345".to_string()).unwrap_err(), "Line 2, column 7: `0x12345` is not a hex word.");
+    assert_eq!(hex_to_image("dead 0010:".to_string()).unwrap_err(), "Line 1, column 6: an address can only start a line.");
+    assert_eq!(hex_to_image("1000: dead".to_string()).unwrap_err(), "Line 1, column 1: `1000` is not an address in memory.");
+    assert_eq!(hex_to_image("0fff: dead beef".to_string()).unwrap_err(), "Line 1, column 12: word runs past the end of memory.");
+}
+
+/// Writes an image as an addressed hex listing, eight words to a line, that `hex_to_image` reads back.
+pub fn image_to_hex(image: &MemoryImage) -> String {
+    let mut out = String::new();
+    for (i, line) in image.to_words().chunks(8).enumerate() {
+        let values: Vec<String> = line.iter().map(|word| word.map_or("----".to_string(), |word| word.to_string())).collect();
+        out += &format!("{:04x}: {}\n", i * 8, values.join(" "));
+    }
+    out
+}
+
+#[test]
+fn test_image_to_hex() {
+    let mut words = vec![Some(Word::new(0xdead)), None, Some(Word::new(0x42))];
+    words.resize(9, Some(Word::new(0)));
+    let image = MemoryImage::from_words(&words);
+    let hex = image_to_hex(&image);
+    assert_eq!(hex, "0000: dead ---- 0042 0000 0000 0000 0000 0000\n0008: 0000\n");
+    assert_eq!(hex_to_image(hex).unwrap(), image);
+}
+
+/// Dumps an image as bytes the way `xxd` does, sixteen bytes to a line with a
+/// byte offset and the printable characters alongside.
+pub fn image_to_xxd(image: &MemoryImage, endian: Endian) -> String {
+    let bytes = image_to_binary(image, endian);
+    let mut out = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let pairs: Vec<String> = line.chunks(2).map(|pair| pair.iter().map(|b| format!("{:02x}", b)).collect()).collect();
+        let text: String = line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        out += &format!("{:08x}: {:<39}  {}\n", i * 16, pairs.join(" "), text);
+    }
+    out
+}
+
+#[test]
+fn test_image_to_xxd() {
+    let image = MemoryImage::from_words(&[Some(Word::new(0xdead)), None, Some(Word::new(0x4142))]);
+    assert_eq!(image_to_xxd(&image, Endian::Big), format!("00000000: {:<39}  ....AB\n", "dead 0000 4142"));
+    assert_eq!(image_to_xxd(&image, Endian::Little), format!("00000000: {:<39}  ....BA\n", "adde 0000 4241"));
+}
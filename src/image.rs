@@ -0,0 +1,237 @@
+use crate::{binary::{SectionKind, CODE_ADDRESS}, word::Word};
+
+/// Words in the VM's address space.
+pub const MEMORY_SIZE: usize = 0x1000;
+
+/// Word the VM starts running from, as set by an image's entry point.
+pub const PC_ADDRESS: u16 = 0x3d;
+
+/// Parses a word address in hex, with an optional `0x` prefix.
+pub fn parse_address(arg: &str) -> Result<u16, String> {
+    let digits = arg.strip_prefix("0x").unwrap_or(arg);
+    match u16::from_str_radix(digits, 16) {
+        Ok(address) if (address as usize) < MEMORY_SIZE => Ok(address),
+        _ => Err(format!("`{arg}` is not an address in memory.")),
+    }
+}
+
+/// A named span of the address space and whether it holds memory or code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub kind: SectionKind,
+    pub start: u16,
+    pub len: u16,
+}
+
+impl Region {
+    pub fn contains(&self, addr: u16) -> bool {
+        addr >= self.start && (addr - self.start) < self.len
+    }
+}
+
+/// The VM's 4096-word address space, with a mask of which words are written.
+///
+/// Addresses wrap at the end of memory, as they do in the VM. Words that
+/// aren't written are left as they are when the image is loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryImage {
+    words: Vec<u16>,
+    written: Vec<bool>,
+    pub regions: Vec<Region>,
+    /// Program counter to start at, if the image sets one.
+    pub entry: Option<u16>,
+}
+
+impl Default for MemoryImage {
+    fn default() -> Self {
+        MemoryImage::new()
+    }
+}
+
+impl MemoryImage {
+    pub fn new() -> Self {
+        MemoryImage { words: vec![0; MEMORY_SIZE], written: vec![false; MEMORY_SIZE], regions: Vec::new(), entry: None }
+    }
+
+    /// Builds an image from words starting at address 0, where `None` is unwritten.
+    pub fn from_words(words: &[Option<Word>]) -> Self {
+        let mut image = MemoryImage::new();
+        for (addr, word) in words.iter().enumerate() {
+            if let Some(word) = word {
+                image.set(addr as u16, *word);
+            }
+        }
+        image
+    }
+
+    pub fn get(&self, addr: u16) -> Option<Word> {
+        let addr = addr as usize % MEMORY_SIZE;
+        self.written[addr].then(|| Word::new(self.words[addr]))
+    }
+
+    pub fn set(&mut self, addr: u16, word: Word) {
+        let addr = addr as usize % MEMORY_SIZE;
+        self.words[addr] = word.value();
+        self.written[addr] = true;
+    }
+
+    /// One past the last written address.
+    pub fn len(&self) -> usize {
+        self.written.iter().rposition(|&w| w).map_or(0, |last| last + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Words up to the last written one, with `None` for unwritten words.
+    pub fn to_words(&self) -> Vec<Option<Word>> {
+        (0..self.len()).map(|addr| self.get(addr as u16)).collect()
+    }
+
+    /// Words up to the last written one, with unwritten words as zero.
+    pub fn dense(&self) -> Vec<Word> {
+        self.words[..self.len()].iter().map(|&w| Word::new(w)).collect()
+    }
+
+    /// Writes the entry point, if there is one, to the program counter word.
+    /// Returns what the image wrote there before if that was something other
+    /// than the entry point or zero.
+    pub fn write_entry(&mut self) -> Option<Word> {
+        let entry = Word::new(self.entry?);
+        let previous = self.get(PC_ADDRESS).filter(|&word| word != entry && word.value() != 0);
+        self.set(PC_ADDRESS, entry);
+        previous
+    }
+
+    pub fn add_region(&mut self, name: &str, kind: SectionKind, start: u16, len: u16) {
+        self.regions.push(Region { name: name.to_string(), kind, start, len });
+    }
+
+    fn region_at(&self, addr: u16) -> Option<&Region> {
+        self.regions.iter().find(|region| region.contains(addr))
+    }
+
+    /// Runs of written words, as `(kind, region name, address, words)`.
+    ///
+    /// Runs break at gaps and region boundaries. Words outside any region are
+    /// memory below `CODE_ADDRESS` and code from it onwards.
+    pub fn sections(&self) -> Vec<(SectionKind, Option<String>, u16, Vec<Word>)> {
+        let label = |addr: u16| match self.region_at(addr) {
+            Some(region) => (region.kind, Some(region.name.clone()), region.start),
+            None if addr < CODE_ADDRESS => (SectionKind::Memory, None, 0),
+            None => (SectionKind::Code, None, CODE_ADDRESS),
+        };
+
+        let mut out: Vec<(SectionKind, Option<String>, u16, Vec<Word>)> = Vec::new();
+        let mut last = None;
+        for addr in 0..self.len() as u16 {
+            let Some(word) = self.get(addr) else {
+                last = None;
+                continue;
+            };
+            let here = label(addr);
+            match out.last_mut() {
+                Some(section) if last.as_ref() == Some(&here) => section.3.push(word),
+                _ => out.push((here.0, here.1.clone(), addr, vec![word])),
+            }
+            last = Some(here);
+        }
+        out
+    }
+
+    /// The words a chat command writes as `kind`: those in regions of that
+    /// kind. An image without regions is all memory.
+    pub fn section(&self, kind: SectionKind) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        for addr in 0..self.len() as u16 {
+            let in_section = match self.region_at(addr) {
+                Some(region) => region.kind == kind,
+                None if self.regions.is_empty() => kind == SectionKind::Memory,
+                None => (addr < CODE_ADDRESS) == (kind == SectionKind::Memory),
+            };
+            if let Some(word) = self.get(addr).filter(|_| in_section) {
+                image.set(addr, word);
+            }
+        }
+        image
+    }
+
+    /// The words from `start` onwards, moved down to address 0.
+    pub fn tail(&self, start: u16) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        for addr in start as usize..self.len() {
+            if let Some(word) = self.get(addr as u16) {
+                image.set((addr - start as usize) as u16, word);
+            }
+        }
+        image
+    }
+}
+
+#[test]
+fn test_memory_image() {
+    let mut image = MemoryImage::from_words(&[Some(Word::new(0xdead)), None, Some(Word::new(0))]);
+    assert_eq!(image.len(), 3);
+    assert_eq!(image.get(1), None);
+    assert_eq!(image.get(2), Some(Word::new(0)));
+    assert_eq!(image.dense(), vec![Word::new(0xdead), Word::new(0), Word::new(0)]);
+
+    // Addresses wrap at the end of memory.
+    image.set(0x1001, Word::new(0xbeef));
+    assert_eq!(image.get(1), Some(Word::new(0xbeef)));
+    assert_eq!(image.to_words(), vec![Some(Word::new(0xdead)), Some(Word::new(0xbeef)), Some(Word::new(0))]);
+
+    assert!(MemoryImage::new().is_empty());
+}
+
+#[test]
+fn test_write_entry() {
+    let mut image = MemoryImage::from_words(&[Some(Word::new(0xdead))]);
+    assert_eq!(image.write_entry(), None);
+    assert_eq!(image.len(), 1);
+
+    // Zeroes there are only padding, so they're replaced without a warning.
+    image.set(PC_ADDRESS, Word::new(0));
+
+    image.entry = Some(0x40);
+    assert_eq!(image.write_entry(), None);
+    assert_eq!(image.get(PC_ADDRESS), Some(Word::new(0x40)));
+    assert_eq!(image.write_entry(), None);
+
+    image.entry = Some(0x80);
+    assert_eq!(image.write_entry(), Some(Word::new(0x40)));
+    assert_eq!(image.get(PC_ADDRESS), Some(Word::new(0x80)));
+
+    assert_eq!(parse_address("0x3d"), Ok(0x3d));
+    assert_eq!(parse_address("1000"), Err("`1000` is not an address in memory.".to_string()));
+}
+
+#[test]
+fn test_memory_image_sections() {
+    let mut image = MemoryImage::new();
+    for addr in [0, 1, 0x3f, 0x40, 0x41, 0x100] {
+        image.set(addr, Word::new(addr));
+    }
+    let words = |values: &[u16]| values.iter().map(|&v| Word::new(v)).collect::<Vec<_>>();
+    assert_eq!(image.sections(), vec![
+        (SectionKind::Memory, None, 0, words(&[0, 1])),
+        (SectionKind::Memory, None, 0x3f, words(&[0x3f])),
+        (SectionKind::Code, None, 0x40, words(&[0x40, 0x41])),
+        (SectionKind::Code, None, 0x100, words(&[0x100])),
+    ]);
+    assert_eq!(image.section(SectionKind::Memory), image);
+    assert!(image.section(SectionKind::Code).is_empty());
+
+    image.add_region("data", SectionKind::Memory, 0x41, 0x10);
+    assert_eq!(image.sections()[2..], [
+        (SectionKind::Code, None, 0x40, words(&[0x40])),
+        (SectionKind::Memory, Some("data".to_string()), 0x41, words(&[0x41])),
+        (SectionKind::Code, None, 0x100, words(&[0x100])),
+    ]);
+    let code = image.section(SectionKind::Code).tail(CODE_ADDRESS);
+    assert_eq!(code.to_words()[0], Some(Word::new(0x40)));
+    assert_eq!(code.get(1), None);
+    assert_eq!(code.get(0xc0), Some(Word::new(0x100)));
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{binary::SectionKind, image::{MemoryImage, MEMORY_SIZE}, word::Word};
+
+/// A memory image as JSON, for scripts and dashboards.
+///
+/// ```json
+/// {
+///   "base": 0,
+///   "source": "demo.w2s",
+///   "entry": 64,
+///   "regions": [
+///     { "address": 0, "kind": "memory", "name": "data", "words": ["dead", "beef"] },
+///     { "address": 64, "kind": "code", "words": ["0042"] }
+///   ]
+/// }
+/// ```
+///
+/// Region addresses are relative to `base`. Everything but `regions` and their
+/// `address` and `words` is optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonImage {
+    #[serde(default)]
+    pub base: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<u16>,
+    pub regions: Vec<JsonRegion>,
+}
+
+/// A run of words and the address it loads to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRegion {
+    pub address: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SectionKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub words: Vec<String>,
+}
+
+impl JsonImage {
+    /// Parses an image, checking every word is hex and lands in memory.
+    pub fn parse(text: &str) -> Result<JsonImage, String> {
+        let image: JsonImage = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        for (i, region) in image.regions.iter().enumerate() {
+            for word in &region.words {
+                if word.is_empty() || word.len() > 4 || !word.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("Region {i}: `{word}` is not a hex word."));
+                }
+            }
+            if image.base as usize + region.address as usize + region.words.len() > MEMORY_SIZE {
+                return Err(format!("Region {i} runs past the end of memory."));
+            }
+        }
+        Ok(image)
+    }
+
+    /// Builds JSON with a region per section of the image.
+    pub fn from_image(image: &MemoryImage, source: Option<String>) -> JsonImage {
+        let regions = image.sections().into_iter()
+            .map(|(kind, name, address, words)| JsonRegion {
+                address,
+                kind: Some(kind),
+                name,
+                words: words.iter().map(|w| w.to_string()).collect(),
+            })
+            .collect();
+        JsonImage { base: 0, source, entry: image.entry, regions }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap() + "\n"
+    }
+
+    /// Lays the regions out as an image. Regions with a kind become regions
+    /// of the image, so an image without any kinds is all memory.
+    pub fn image(&self) -> MemoryImage {
+        let mut image = MemoryImage::new();
+        image.entry = self.entry;
+        for (i, region) in self.regions.iter().enumerate() {
+            let start = self.base.wrapping_add(region.address);
+            for (offset, word) in region.words.iter().enumerate() {
+                image.set(start.wrapping_add(offset as u16), Word::new(u16::from_str_radix(word, 16).unwrap()));
+            }
+            if let Some(kind) = region.kind {
+                let name = region.name.clone().unwrap_or_else(|| format!("region {i}"));
+                image.add_region(&name, kind, start, region.words.len() as u16);
+            }
+        }
+        image
+    }
+}
+
+#[test]
+fn test_json_image() {
+    let text = r#"{"base": 16, "entry": 64, "regions": [
+        {"address": 0, "words": ["dead", "beef"]},
+        {"address": 3, "kind": "code", "name": "main", "words": ["42"]}
+    ]}"#;
+    let image = JsonImage::parse(text).unwrap().image();
+    assert_eq!(image.entry, Some(64));
+    let mut words = vec![None; 16];
+    words.extend([Some(Word::new(0xdead)), Some(Word::new(0xbeef)), None, Some(Word::new(0x42))]);
+    assert_eq!(image.to_words(), words);
+    assert_eq!(image.regions[0].name, "main");
+    assert_eq!(image.section(SectionKind::Code).len(), 20);
+
+    let json = JsonImage::from_image(&image, Some("demo.w2s".to_string()));
+    assert_eq!(json.to_json(), concat!(
+        r#"{"base":0,"source":"demo.w2s","entry":64,"regions":["#,
+        r#"{"address":16,"kind":"memory","words":["dead","beef"]},"#,
+        r#"{"address":19,"kind":"code","name":"main","words":["0042"]}]}"#, "\n",
+    ));
+    assert_eq!(JsonImage::parse(&json.to_json()).unwrap().image().to_words(), words);
+
+    let image = JsonImage::parse(r#"{"regions": [{"address": 0, "words": ["1"]}]}"#).unwrap().image();
+    assert!(image.regions.is_empty());
+
+    assert_eq!(
+        JsonImage::parse(r#"{"regions": [{"address": 0, "words": ["dead", "xyz"]}]}"#).unwrap_err(),
+        "Region 0: `xyz` is not a hex word."
+    );
+    assert_eq!(
+        JsonImage::parse(r#"{"regions": [{"address": 4095, "words": ["dead", "beef"]}]}"#).unwrap_err(),
+        "Region 0 runs past the end of memory."
+    );
+    assert!(JsonImage::parse(r#"{"regions": 3}"#).is_err());
+}
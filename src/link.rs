@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+use crate::{binary::SectionKind, image::{parse_address, MemoryImage, MEMORY_SIZE}};
+
+/// What to do when linked inputs write the same address.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Overlap {
+    /// Refuse to link
+    Error,
+    /// Keep the word from the earlier input
+    First,
+    /// Keep the word from the later input
+    Last,
+}
+
+/// An input file and the word address its image is placed at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkInput {
+    pub path: PathBuf,
+    pub origin: u16,
+}
+
+impl LinkInput {
+    /// Parses `path[@address]`, with the address in hex and defaulting to 0.
+    pub fn from_arg(arg: &str) -> Result<LinkInput, String> {
+        let Some((path, address)) = arg.rsplit_once('@') else {
+            return Ok(LinkInput { path: PathBuf::from(arg), origin: 0 });
+        };
+        Ok(LinkInput { path: PathBuf::from(path), origin: parse_address(address)? })
+    }
+}
+
+/// Lays images into a single one, each shifted to its origin. `images` holds
+/// a name to report each by, its origin and the image. Regions move with their
+/// words, and an image without regions becomes a memory region per run, named
+/// after it. The entry point comes from the first image that sets one.
+pub fn link(images: &[(String, u16, MemoryImage)], overlap: Overlap) -> Result<MemoryImage, String> {
+    let mut linked = MemoryImage::new();
+    let mut owners = vec![0; MEMORY_SIZE];
+
+    for (i, (name, origin, image)) in images.iter().enumerate() {
+        for offset in 0..image.len() {
+            let Some(word) = image.get(offset as u16) else { continue };
+            let addr = *origin as usize + offset;
+            if addr >= MEMORY_SIZE {
+                return Err(format!("{name} runs past the end of memory at {:04x}.", addr));
+            }
+            if linked.get(addr as u16).is_some() {
+                match overlap {
+                    Overlap::Error => return Err(format!("{name} overlaps {} at {:04x}.", images[owners[addr]].0, addr)),
+                    Overlap::First => continue,
+                    Overlap::Last => {}
+                }
+            }
+            linked.set(addr as u16, word);
+            owners[addr] = i;
+        }
+
+        if image.regions.is_empty() {
+            for (_, _, address, words) in image.sections() {
+                linked.add_region(name, SectionKind::Memory, address + origin, words.len() as u16);
+            }
+        }
+        for region in &image.regions {
+            linked.add_region(&region.name, region.kind, region.start + origin, region.len);
+        }
+        linked.entry = linked.entry.or(image.entry.map(|entry| entry.wrapping_add(*origin)));
+    }
+
+    Ok(linked)
+}
+
+#[test]
+fn test_link_input_from_arg() {
+    assert_eq!(LinkInput::from_arg("boot.w2s"), Ok(LinkInput { path: PathBuf::from("boot.w2s"), origin: 0 }));
+    assert_eq!(LinkInput::from_arg("a@b.hex@0x40"), Ok(LinkInput { path: PathBuf::from("a@b.hex"), origin: 0x40 }));
+    assert_eq!(LinkInput::from_arg("data.hex@100"), Ok(LinkInput { path: PathBuf::from("data.hex"), origin: 0x100 }));
+    assert_eq!(LinkInput::from_arg("data.hex@1000").unwrap_err(), "`1000` is not an address in memory.");
+}
+
+#[test]
+fn test_link() {
+    use crate::word::Word;
+
+    let word = |value| Some(Word::new(value));
+    let images = vec![
+        ("a".to_string(), 0, MemoryImage::from_words(&[word(1), None, word(2)])),
+        ("b".to_string(), 1, MemoryImage::from_words(&[word(3), word(4)])),
+    ];
+    assert_eq!(link(&images, Overlap::Error).unwrap_err(), "b overlaps a at 0002.");
+    assert_eq!(link(&images, Overlap::First).unwrap().to_words(), vec![word(1), word(3), word(2)]);
+    let linked = link(&images, Overlap::Last).unwrap();
+    assert_eq!(linked.to_words(), vec![word(1), word(3), word(4)]);
+    assert_eq!(linked.regions.iter().map(|r| (r.name.as_str(), r.start, r.len)).collect::<Vec<_>>(), vec![
+        ("a", 0, 1), ("a", 2, 1), ("b", 1, 2),
+    ]);
+
+    let mut code = MemoryImage::from_words(&[word(5)]);
+    code.add_region("code", SectionKind::Code, 0, 1);
+    code.entry = Some(0);
+    let linked = link(&[("c".to_string(), 0x40, code)], Overlap::Error).unwrap();
+    assert_eq!(linked.regions[0].start, 0x40);
+    assert_eq!(linked.entry, Some(0x40));
+    assert_eq!(linked.section(SectionKind::Code).get(0x40), word(5));
+
+    let images = vec![("c".to_string(), 0xfff, MemoryImage::from_words(&[word(1), word(2)]))];
+    assert_eq!(link(&images, Overlap::Error).unwrap_err(), "c runs past the end of memory at 1000.");
+}
@@ -2,16 +2,30 @@ mod tokens;
 mod word;
 mod diff;
 mod reverse;
-
-use std::{io::Read, path::PathBuf};
+mod decoder;
+mod alphabet;
+mod binary;
+mod explain;
+mod verify;
+mod records;
+mod json;
+mod arrays;
+mod link;
+mod image;
+mod template;
+mod replay;
+mod hex;
+mod definition;
+
+use std::{io::{Read, Write}, path::{Path, PathBuf}};
 use clap_stdin::{FileOrStdin, StdinError};
 use word::Word;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use tokens::WordGroupConstructor;
 
 // use crate::tokens::{char_to_rune, WordGroup};
-use crate::{reverse::reverse_write, tokens::{string_to_rune, WordGroup}};
+use crate::{alphabet::{Alphabet, Symbol}, arrays::{image_to_array, Language}, binary::{binary_to_words, image_to_binary, parse_binary_file, BinaryFile, Endian, Pad, SectionKind, CODE_ADDRESS}, explain::explain, hex::{hex_to_image, image_to_hex, image_to_xxd}, image::{parse_address, MemoryImage, PC_ADDRESS}, json::JsonImage, link::{link, LinkInput, Overlap}, records::{ihex_to_image, image_to_ihex, image_to_srec, srec_to_image}, replay::replay, reverse::{reverse_write, reverse_write_counted, written_listing}, template::CommandTemplate, tokens::{rune_to_string, string_to_rune, WordGroup}, verify::verify};
 
 #[allow(unused)]
 const ANSI_RESET: &str = "\x1B[0m";
@@ -22,6 +36,77 @@ const ANSI_GREY: &str = "\x1B[37m";
 #[allow(unused)]
 const ANSI_BLUE: &str = "\x1B[34m";
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// Rune text, or comma separated words when reversing
+    Runes,
+    /// Addressed hex listing, as read back by default
+    Hex,
+    /// xxd style dump, in the byte order given by `--endian`
+    Xxd,
+    /// Wave2 binary file
+    W2s,
+    /// Intel HEX
+    Ihex,
+    /// Motorola S-records
+    Srec,
+    /// Raw words, in the byte order given by `--endian`
+    Raw,
+    /// JSON memory image
+    Json,
+    /// C `uint16_t` array
+    C,
+    /// Rust `u16` array constant
+    Rust,
+    /// JavaScript `Uint16Array`
+    Js,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Source {
+    /// Hex words
+    Hex,
+    /// Wave2 binary file
+    W2s,
+    /// Intel HEX
+    Ihex,
+    /// Motorola S-records
+    Srec,
+    /// Raw words loaded from address 0, in the byte order given by `--endian`
+    Raw,
+    /// JSON memory image
+    Json,
+    /// Runes, decoded into the words they write
+    Runes,
+    /// Log of chat commands, replayed into the memory they leave
+    Chat,
+}
+
+/// What `--max-length` counts.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum LengthUnit {
+    /// Unicode characters
+    Chars,
+    /// UTF-8 bytes, three for each rune
+    Bytes,
+}
+
+impl LengthUnit {
+    fn measure(self, text: &str) -> usize {
+        match self {
+            LengthUnit::Chars => text.chars().count(),
+            LengthUnit::Bytes => text.len(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LengthUnit::Chars => "character",
+            LengthUnit::Bytes => "byte",
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
@@ -31,10 +116,59 @@ struct Args {
     /// Read input file as Wave2 binary format
     #[arg(short, long, default_value_t = false)]
     binary: bool,
+    /// Input format (`-b` is short for `--from w2s`)
+    #[arg(long, default_value = "hex")]
+    from: Source,
+    /// Byte order of raw input and output
+    #[arg(long, default_value = "big")]
+    endian: Endian,
+    /// How to treat an odd trailing byte of raw input
+    #[arg(long, default_value = "error")]
+    pad: Pad,
+    /// Link inputs into one image instead of reading INPUT, each placed at an
+    /// optional hex word address (format from extension, else `--from`)
+    #[arg(short = 'L', long, value_name = "FILE[@ADDRESS]", value_parser = LinkInput::from_arg)]
+    link: Vec<LinkInput>,
+    /// How to resolve linked inputs writing the same address
+    #[arg(long, default_value = "error")]
+    overlap: Overlap,
+    /// Replay a chat log only up to this line
+    #[arg(long, value_name = "LINE")]
+    until: Option<usize>,
+    /// Word address to start running from, written to the program counter unless the output format has an entry point
+    /// (defaults to the input's entry point, if it has one, like the code section of a w2s file)
+    #[arg(long, value_parser = parse_address)]
+    entry: Option<u16>,
+
     /// Output as chat command
     #[arg(short, long, default_value_t = false)]
     chat: bool,
 
+    /// Commands to write chat output with (vm, vm-keep, or a template file)
+    #[arg(short, long, value_parser = CommandTemplate::from_arg, default_value = "vm")]
+    template: CommandTemplate,
+
+    /// Longest chat message to write, splitting into several commands past it
+    #[arg(long, default_value_t = 500)]
+    max_length: usize,
+
+    /// What `--max-length` counts
+    #[arg(long, default_value = "chars")]
+    length_unit: LengthUnit,
+
+    /// Search for the shortest possible rune encoding
+    #[arg(short = 'O', long, default_value_t = false)]
+    optimize: bool,
+
+    /// Decode the generated runes and check they reproduce the input
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// Characters to write encoded output in
+    /// (runes, ascii, or an alphabet definition file)
+    #[arg(short, long, value_parser = Alphabet::from_arg, default_value = "runes")]
+    alphabet: Alphabet,
+
     /// Colorize output
     #[arg(short = 'C', long = "color", default_value_t = false)]
     color: bool,
@@ -43,6 +177,18 @@ struct Args {
     #[arg(short, long, default_value_t = false)]
     reverse: bool,
 
+    /// With `-r`, list the addresses the runes write and how many times
+    #[arg(short, long, default_value_t = false)]
+    written: bool,
+
+    /// Deflate runes into hex words that can be read back as input
+    #[arg(short, long, default_value_t = false)]
+    deflate: bool,
+
+    /// List each rune group with the addresses and words it writes
+    #[arg(short, long, default_value_t = false)]
+    explain: bool,
+
     /// Reverse encode the runes into a human readable representation
     #[arg(short='R', long="read",  default_value_t = false)]
     read_runes: bool,
@@ -50,6 +196,18 @@ struct Args {
     // #[arg(short, long)]
     // diff: Option<PathBuf>,
 
+    /// Output format
+    #[arg(long)]
+    to: Option<Format>,
+
+    /// Name of the array written by the c, rust and js formats
+    #[arg(long, default_value = "image")]
+    array_name: String,
+
+    /// Comment each array row with its starting address
+    #[arg(long, default_value_t = false)]
+    address_comments: bool,
+
     /// Output file path
     #[arg(short, long)]
     output: Option<PathBuf>,
@@ -58,27 +216,23 @@ struct Args {
 fn main() -> Result<(), StdinError> {
     let args = Args::parse();
 
+    let image_options = ImageOptions {
+        source: args.input.is_file().then(|| args.input.filename().to_string()),
+        endian: args.endian,
+        array_name: args.array_name.clone(),
+        address_comments: args.address_comments,
+    };
     let mut reader = args.input.into_reader()?;
 
-    let words;
-    let mem_words;
-    let code_words;
-
     if args.read_runes {
         let mut runes = String::new();
         reader.read_to_string(&mut runes)?;
 
         for c in runes.chars() {
-            print!("{}", match c {
-                'ᚺ' => "0", 'ᚾ' => "1", 'ᛁ' => "2", 'ᛃ' => "3",
-                'ᛈ' => "4", 'ᛇ' => "5", 'ᛉ' => "6", 'ᛊ' => "7",
-                'ᛏ' => "8", 'ᛒ' => "9", 'ᛖ' => "a", 'ᛗ' => "b",
-                'ᛚ' => "c", 'ᛜ' => "d", 'ᛞ' => "e", 'ᛟ' => "f",
-                'ᚱ' => "*", 'ᚠ' => "z",
-                'ᚲ' => "<", '×' => ">",
-                'ᚢ' => "_",
-                '\n' | ' ' => "",
-                _ => "?"
+            print!("{}", match args.alphabet.decode(c) {
+                Some(symbol) => symbol.notation().to_string(),
+                None if c == '\n' || c == ' ' => String::new(),
+                None => "?".to_string(),
             });
         }
     }
@@ -86,46 +240,90 @@ fn main() -> Result<(), StdinError> {
         let mut runes = String::new();
         reader.read_to_string(&mut runes)?;
 
-        let words = reverse_write(runes);
+        let (image, counts) = reverse_write_counted(args.alphabet.to_runes(&runes));
+
+        if args.written {
+            let listing = written_listing(&counts);
+            match args.output {
+                Some(output_path) => std::fs::write(output_path, listing + "\n").expect("Failed to write output file"),
+                None => println!("{}", listing),
+            }
+            return Ok(())
+        }
+
+        if let Some(format) = args.to.filter(|format| *format != Format::Runes) {
+            write_image(format, &image, &image_options, args.output);
+            return Ok(())
+        }
 
-        for chunk in words.chunks(16) {
+        for chunk in image.dense().chunks(16) {
             let values: Vec<String> = chunk.iter().map(|word| word.to_string()).collect();
             println!("{}", values.join(", "));
         }
         return Ok(())
     }
+    if args.explain {
+        let mut runes = String::new();
+        reader.read_to_string(&mut runes)?;
 
-    if args.binary {
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer)?;
+        let listing = explain(&runes, &args.alphabet);
 
-        let file = parse_binary_file(buffer).expect("Failed to parse binary file");
+        if let Some(output_path) = args.output {
+            std::fs::write(output_path, listing).expect("Failed to write output file");
+        } else {
+            println!("{}", listing);
+        }
+        return Ok(())
+    }
+    if args.deflate {
+        let mut runes = String::new();
+        reader.read_to_string(&mut runes)?;
 
-        let size = match (file.memory_start, file.code_start) {
-            (0, 0) => 0,
-            (_, 0) => file.memory.len(),
-            (_, _) => 0x80 + file.code.len()
-        };
-        let mut bytes = Vec::new();
-        bytes.resize(size, 0);
+        let hex = rune_to_string(args.alphabet.to_runes(&runes)).unwrap_or_else(|err| {
+            eprintln!("Failed to deflate runes: {err}");
+            std::process::exit(1);
+        });
 
-        for i in 0..file.memory.len() {
-            bytes[i] = file.memory[i];
-        }
-        for i in 0..file.code.len() {
-            bytes[i + 0x80] = file.code[i];
+        if let Some(output_path) = args.output {
+            std::fs::write(output_path, hex).expect("Failed to write output file");
+        } else {
+            println!("{}", hex);
         }
+        return Ok(())
+    }
 
-        words = binary_to_words(bytes);
-        mem_words = binary_to_words(file.memory);
-        code_words = binary_to_words(file.code);
+    let source = if args.binary { Source::W2s } else { args.from };
+    let load_options = LoadOptions {
+        endian: args.endian,
+        pad: args.pad,
+        alphabet: &args.alphabet,
+        template: &args.template,
+        until: args.until,
+    };
+    let input = if args.link.is_empty() {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        load_input(source, buffer, &load_options)
     } else {
-        let mut input = String::new();
-        reader.read_to_string(&mut input)?;
+        link_inputs(&args.link, source, args.overlap, &load_options)
+    };
+    let mut image = input.unwrap_or_else(|err| {
+        eprintln!("Failed to read input: {err}");
+        std::process::exit(1);
+    });
+    if args.entry.is_some() {
+        image.entry = args.entry;
+    }
 
-        words = hex_to_words(input.clone());
-        mem_words = hex_to_words(input.clone());
-        code_words = Vec::new();
+    // Formats with an entry point of their own keep it there, the rest carry it in the program counter.
+    let keeps_entry = matches!(args.to, Some(Format::W2s | Format::Ihex | Format::Srec | Format::Json));
+    if !keeps_entry && let Some(previous) = image.write_entry() {
+        eprintln!("Warning: input writes {previous} to the program counter at {PC_ADDRESS:04x}, replacing it with the entry point {:04x}", image.entry.unwrap());
+    }
+
+    if let Some(format) = args.to.filter(|format| *format != Format::Runes) {
+        write_image(format, &image, &image_options, args.output);
+        return Ok(())
     }
 
     // TODO: Implement decompiling runes
@@ -137,45 +335,51 @@ fn main() -> Result<(), StdinError> {
 
     // }
 
-    let mut output = String::new();
-
-    let mem_groups = WordGroupConstructor::new(mem_words).construct();
-    let code_groups = WordGroupConstructor::new(code_words).construct();
+    let output;
 
-    const CHUNK_LIMIT: usize = 64;
+    let mem_image = image.section(SectionKind::Memory);
+    let code_image = image.section(SectionKind::Code).tail(CODE_ADDRESS);
+    let mem_groups = construct(&mut WordGroupConstructor::from_image(&mem_image), args.optimize).filter(|_| !mem_image.is_empty());
+    let code_groups = construct(&mut WordGroupConstructor::from_image(&code_image), args.optimize).filter(|_| !code_image.is_empty());
 
     if args.chat {
-        if words.len() > CHUNK_LIMIT {
-            // Split long sequences into multiple commands.
-            let mut offset = 0;
-            let mut first = true;
-            for chunk in words.chunks(CHUNK_LIMIT) {
-                if !first { output += "\n"; }
-                let mut chunk = chunk.to_vec();
-                if first {
-                    // If writing 0 to PC, write 0x40 instead.
-                    if chunk[0x3d].value() == 0 {
-                        chunk[0x3d] = Word::new(0x40);
-                    }
-                }
-                let mut ctor = WordGroupConstructor::new(chunk);
-                let groups = ctor.construct().unwrap();
-                output += &write_command(first, false, offset, Some(words_to_string(groups, args.color)), None);
-                offset += ctor.word_count;
-                first = false;
-            }
-            output += " ! restart";
+        if args.verify {
+            if let Some(mem) = &mem_groups { check("", mem, 0, &mem_image, &args.alphabet); }
+            if let Some(code) = &code_groups { check("", code, 0, &code_image, &args.alphabet); }
+        }
+        let single = |color| match (&mem_groups, &code_groups) {
+            (Some(mem), None) => write_command(&args.template, true, true, 0, Some(render(mem.clone(), color, &args.alphabet)), None),
+            (None, Some(code)) => write_command(&args.template, true, true, 0, None, Some(render(code.clone(), color, &args.alphabet))),
+            (Some(mem), Some(code)) => write_command(&args.template, true, true, 0, Some(render(mem.clone(), color, &args.alphabet)), Some(render(code.clone(), color, &args.alphabet))),
+            _ => String::new(),
+        };
+
+        if args.length_unit.measure(&single(false)) <= args.max_length {
+            output = single(args.color);
         } else {
-            output = match (mem_groups, code_groups) {
-                (Some(mem), None) => write_command(true, true, 0, Some(words_to_string(mem, args.color)), None),
-                (None, Some(code)) => write_command(true, true, 0, None, Some(words_to_string(code, args.color))),
-                (Some(mem), Some(code)) => write_command(true, true, 0, Some(words_to_string(mem, args.color)), Some(words_to_string(code, args.color))),
-                _ => output
-            }
+            // Split long sequences into as many commands as it takes to fit.
+            let chunks = split_commands(&image, &args.template, args.max_length, args.length_unit, args.optimize, &args.alphabet).unwrap_or_else(|err| {
+                eprintln!("Failed to split chat command: {err}");
+                std::process::exit(1);
+            });
+            let commands: Vec<String> = chunks.into_iter().map(|chunk| {
+                if chunk.offset > 0 && args.alphabet.encode(Symbol::Skip).is_none() {
+                    eprintln!("The alphabet can't skip to the offset of a split command, use runes instead");
+                    std::process::exit(1);
+                }
+                if args.verify {
+                    check(&make_rune_offset(chunk.offset), &chunk.words, chunk.offset, &chunk.image, &args.alphabet);
+                }
+                chunk.command(&args.template, render(chunk.words.clone(), args.color, &args.alphabet))
+            }).collect();
+            output = commands.join("\n") + &args.template.restart_suffix();
         }
     } else {
-        let all_groups = WordGroupConstructor::new(words).construct().unwrap();
-        output = words_to_string(all_groups, args.color);
+        let all_groups = construct(&mut WordGroupConstructor::from_image(&image), args.optimize).unwrap();
+        if args.verify {
+            check("", &all_groups, 0, &image, &args.alphabet);
+        }
+        output = render(all_groups, args.color, &args.alphabet);
     }
 
     if let Some(output_path) = args.output {
@@ -186,62 +390,199 @@ fn main() -> Result<(), StdinError> {
     Ok(())
 }
 
-fn words_to_string(words: Vec<WordGroup>, color: bool) -> String {
+/// Settings for reading input images.
+struct LoadOptions<'a> {
+    endian: Endian,
+    pad: Pad,
+    alphabet: &'a Alphabet,
+    /// Commands a chat log is written with.
+    template: &'a CommandTemplate,
+    /// Last line of a chat log to replay.
+    until: Option<usize>,
+}
+
+fn load_input(source: Source, bytes: Vec<u8>, options: &LoadOptions) -> Result<MemoryImage, String> {
+    let text = || String::from_utf8(bytes.clone()).map_err(|_| "Input is not valid UTF-8.".to_string());
+
+    match source {
+        Source::W2s => Ok(parse_binary_file(bytes).map_err(|err| format!("Failed to parse binary file: {err}"))?.image()),
+        Source::Raw => {
+            let words = binary_to_words(bytes, options.endian, options.pad)?;
+            Ok(MemoryImage::from_words(&words.into_iter().map(Some).collect::<Vec<_>>()))
+        }
+        Source::Json => Ok(JsonImage::parse(&text()?).map_err(|err| format!("Failed to parse JSON image: {err}"))?.image()),
+        Source::Ihex => ihex_to_image(&text()?),
+        Source::Srec => srec_to_image(&text()?),
+        Source::Runes => Ok(reverse_write(options.alphabet.to_runes(&text()?))),
+        Source::Chat => {
+            let (image, warnings) = replay(&text()?, options.template, options.alphabet, options.until);
+            for warning in warnings {
+                eprintln!("Warning: {warning}");
+            }
+            Ok(image)
+        }
+        Source::Hex => hex_to_image(text()?),
+    }
+}
+
+/// Picks an input's format from its extension, falling back to `default`.
+fn source_for(path: &Path, default: Source) -> Source {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("w2s") => Source::W2s,
+        Some("ihex") => Source::Ihex,
+        Some("srec" | "s19") => Source::Srec,
+        Some("bin" | "raw") => Source::Raw,
+        Some("json") => Source::Json,
+        Some("runes") => Source::Runes,
+        Some("log") => Source::Chat,
+        _ => default,
+    }
+}
+
+/// Loads each linked input and lays them into one image.
+fn link_inputs(inputs: &[LinkInput], default: Source, overlap: Overlap, options: &LoadOptions) -> Result<MemoryImage, String> {
+    let mut images = Vec::new();
+    for input in inputs {
+        let name = input.path.display().to_string();
+        let bytes = std::fs::read(&input.path).map_err(|err| format!("Failed to read {name}: {err}"))?;
+        let image = load_input(source_for(&input.path, default), bytes, options).map_err(|err| format!("{name}: {err}"))?;
+        images.push((name, input.origin, image));
+    }
+    link(&images, overlap)
+}
+
+/// Settings for writing images in formats other than runes.
+struct ImageOptions {
+    /// Input file the image came from, if it wasn't stdin.
+    source: Option<String>,
+    endian: Endian,
+    array_name: String,
+    address_comments: bool,
+}
+
+/// Writes words as an image file to the output path or stdout, exiting on failure.
+fn write_image(format: Format, image: &MemoryImage, options: &ImageOptions, output: Option<PathBuf>) {
+    let array = |language| image_to_array(image, language, &options.array_name, options.address_comments).into_bytes();
+    let bytes = match format {
+        Format::W2s => {
+            let file = BinaryFile::from_image(image).unwrap_or_else(|err| {
+                eprintln!("Failed to build binary file: {err}");
+                std::process::exit(1);
+            });
+            file.to_bytes()
+        }
+        Format::Ihex => image_to_ihex(image).into_bytes(),
+        Format::Srec => image_to_srec(image).into_bytes(),
+        Format::Raw => image_to_binary(image, options.endian),
+        Format::Hex => image_to_hex(image).into_bytes(),
+        Format::Xxd => image_to_xxd(image, options.endian).into_bytes(),
+        Format::Json => JsonImage::from_image(image, options.source.clone()).to_json().into_bytes(),
+        Format::C => array(Language::C),
+        Format::Rust => array(Language::Rust),
+        Format::Js => array(Language::Js),
+        Format::Runes => unreachable!("runes are written by the encoder"),
+    };
+    if let Some(output_path) = output {
+        std::fs::write(output_path, bytes).expect("Failed to write output file");
+    } else {
+        std::io::stdout().write_all(&bytes).expect("Failed to write output");
+    }
+}
+
+fn construct(ctor: &mut WordGroupConstructor, optimize: bool) -> Option<Vec<WordGroup>> {
+    if optimize { ctor.construct_optimal() }
+    else        { ctor.construct() }
+}
+
+/// Exits with a report if `prefix` followed by `groups` doesn't decode back to `image` at `offset`.
+fn check(prefix: &str, groups: &[WordGroup], offset: u16, image: &MemoryImage, alphabet: &Alphabet) {
+    let runes = prefix.to_string() + &alphabet.to_runes(&render(groups.to_vec(), false, alphabet));
+    if let Err(err) = verify(&runes, offset, image) {
+        eprintln!("Verification failed: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Renders groups in the chosen alphabet, exiting if they can't be expressed in it.
+fn render(groups: Vec<WordGroup>, color: bool, alphabet: &Alphabet) -> String {
+    words_to_string(groups, color, alphabet).unwrap_or_else(|err| {
+        eprintln!("Failed to encode output: {err}");
+        std::process::exit(1);
+    })
+}
+
+fn words_to_string(words: Vec<WordGroup>, color: bool, alphabet: &Alphabet) -> Result<String, String> {
     let mut out = String::new();
     let mut bright = true;
+    // What the groups expect the decoder to repeat, and what it will after spelled out groups.
+    let mut last_written = 0;
+    let mut decoded_last = 0;
     for group in words {
         if color { out += if bright { ANSI_WHITE } else { ANSI_BLUE }; }
-        out += group.to_string().as_str();
+        let repeated = last_written;
+        let digits = group.ascii(&mut last_written);
+        let is_zero = matches!(group, WordGroup::Zero | WordGroup::ZeroChain(_));
+        // Groups the alphabet has no control glyphs for are spelled out in digits,
+        // and so are repeats of a value that spelled out zeroes replaced.
+        let text = match group {
+            WordGroup::Repeat(_) if decoded_last != repeated => None,
+            _ => alphabet.render(&group.notation()),
+        };
+        out += &match text {
+            Some(text) => {
+                if !is_zero { decoded_last = last_written; }
+                text
+            }
+            None => {
+                decoded_last = if is_zero { 0 } else { last_written };
+                alphabet.render(&digits?).ok_or("Alphabet is missing a digit glyph")?
+            }
+        };
         if color { out += ANSI_RESET; }
         bright = !bright;
     }
-    out
+    Ok(out)
 }
 
-fn write_command(clear: bool, reset: bool, offset: u16, mem: Option<String>, code: Option<String>) -> String {
-    let mut out = String::new();
-
-    out += if clear { "!vm clear " }
-           else     { "!vm " };
+fn write_command(template: &CommandTemplate, clear: bool, reset: bool, offset: u16, mem: Option<String>, code: Option<String>) -> String {
+    let mut commands = Vec::new();
 
     match (offset, mem, code) {
         (0, Some(mem), Some(code)) => {
-            out += "write ";
-            out += &mem;
-            out += " ! code ";
-            out += &code;
-        }
-        (0, Some(mem), None) => {
-            out += "write ";
-            out += &mem;
-        }
-        (0x40, Some(code), None) |
-        (0, None, Some(code)) => {
-            out += "code ";
-            out += &code;
-        }
-        (offset, Some(mem), None) => {
-            out += "write ";
-            out += make_rune_offset(offset).as_str();
-            out += &mem;
+            commands.push(format!("{} {mem}", template.write));
+            commands.push(format!("{} {code}", template.code));
         }
+        (offset, Some(mem), None) => commands.push(format!("{} {}{mem}", template.write, make_rune_offset(offset))),
+        (offset, None, Some(code)) => commands.push(format!("{} {}{code}", template.code, make_rune_offset(offset))),
         _ => {} // ???
     }
 
-    if reset { out += " ! reset" }
+    if reset && !template.reset.is_empty() { commands.push(template.reset.clone()) }
 
-    out
+    template.message(clear, &commands)
+}
+
+#[test]
+fn test_words_to_string_partial_alphabet() {
+    let digits: String = (0..16).map(|d| format!("{d:x} = {d:x}\n")).collect();
+    let alphabet = Alphabet::parse(&format!("{digits}repeat = R\n")).unwrap();
+    let image = MemoryImage::from_words(&[0xdead, 0, 0xdead, 0xdead].map(|w| Some(Word::new(w))));
+
+    let groups = WordGroupConstructor::from_image(&image).construct_optimal().unwrap();
+    let text = words_to_string(groups, false, &alphabet).unwrap();
+    assert_eq!(verify(&alphabet.to_runes(&text), 0, &image), Ok(()));
 }
 
 #[test]
 fn text_write_command() {
-    assert_eq!(write_command(true,  false, 0,     Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᚾᛁᛃᛈ");
-    assert_eq!(write_command(true,  false, 0,     None, Some("ᚾᛁᛃᛈ".to_string())), "!vm clear code ᚾᛁᛃᛈ");
-    assert_eq!(write_command(true,  true,  0,     Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᚾᛁᛃᛈ ! reset");
-    assert_eq!(write_command(true,  true,  0,     None, Some("ᚾᛁᛃᛈ".to_string())), "!vm clear code ᚾᛁᛃᛈ ! reset");
-    assert_eq!(write_command(true,  true,  0,     Some("1234".to_string()), Some("5678".to_string())), "!vm clear write 1234 ! code 5678 ! reset");
-    assert_eq!(write_command(true,  true,  0x40,  Some("1234".to_string()), None), "!vm clear code 1234 ! reset");
-    assert_eq!(write_command(true,  true,  0x200, Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᛁᚺᚺᚢᚾᛁᛃᛈ ! reset");
+    let vm = CommandTemplate::vm();
+    assert_eq!(write_command(&vm, true,  false, 0,     Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᚾᛁᛃᛈ");
+    assert_eq!(write_command(&vm, true,  false, 0,     None, Some("ᚾᛁᛃᛈ".to_string())), "!vm clear code ᚾᛁᛃᛈ");
+    assert_eq!(write_command(&vm, true,  true,  0,     Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᚾᛁᛃᛈ ! reset");
+    assert_eq!(write_command(&vm, true,  true,  0,     None, Some("ᚾᛁᛃᛈ".to_string())), "!vm clear code ᚾᛁᛃᛈ ! reset");
+    assert_eq!(write_command(&vm, true,  true,  0,     Some("1234".to_string()), Some("5678".to_string())), "!vm clear write 1234 ! code 5678 ! reset");
+    assert_eq!(write_command(&vm, false, false, 0x10,  None, Some("1234".to_string())), "!vm code ᚾᚺᚢ1234");
+    assert_eq!(write_command(&vm, true,  true,  0x200, Some("ᚾᛁᛃᛈ".to_string()), None), "!vm clear write ᛁᚺᚺᚢᚾᛁᛃᛈ ! reset");
 }
 
 fn make_rune_offset(offset: u16) -> String {
@@ -249,7 +590,7 @@ fn make_rune_offset(offset: u16) -> String {
     let mut out = String::new();
     while off > 0 {
         let diff = off.min(0xfff);
-        out += string_to_rune(format!("{:0x}ᚢ", diff).as_str()).as_str();
+        out += &string_to_rune(&format!("{:0x}ᚢ", diff)).unwrap();
         off -= diff;
     }
     out
@@ -266,132 +607,147 @@ fn test_make_rune_offset() {
     assert_eq!(make_rune_offset(0x2000), "ᛟᛟᛟᚢᛟᛟᛟᚢᛁᚢ");
 }
 
-#[allow(unused)]
+/// Part of a segment written by one chat command, moved down to address 0.
 struct MemoryChunk {
+    pub kind: SectionKind,
     pub words: Vec<WordGroup>,
     pub offset: u16,
+    pub image: MemoryImage,
+    /// Whether this is the first command, which clears the VM.
+    pub clear: bool,
 }
 
-struct BinaryFile {
-    pub header: Vec<u8>,
-    pub memory_start: usize,
-    pub code_start: usize,
-    pub memory: Vec<u8>,
-    pub code: Vec<u8>,
-}
-
-fn parse_binary_file(bytes: Vec<u8>) -> Result<BinaryFile, String> {
-    let mut file = BinaryFile {
-        header: Vec::new(),
-        memory: Vec::new(),
-        memory_start: 0,
-        code_start: 0,
-        code: Vec::new(),
-    };
-
-    // Check header
-    if bytes.len() < 7 {
-        return Err("Malformed header, file too short.".to_string());
+impl MemoryChunk {
+    /// The command writing this chunk, with `text` as its runes.
+    fn command(&self, template: &CommandTemplate, text: String) -> String {
+        match self.kind {
+            SectionKind::Memory => write_command(template, self.clear, false, self.offset, Some(text), None),
+            SectionKind::Code => write_command(template, self.clear, false, self.offset, None, Some(text)),
+        }
     }
+}
 
-    let magic = &bytes[0..4];
-    if magic != b"MWvm" {
-        return Err("Invalid magic number.".to_string());
-    }
+/// Splits an image into chat commands no longer than `max_length`, memory
+/// first with `write` and then code with `code`. Each command packs in as
+/// many words from where the last left off in its segment as fit, and the
+/// last command's length includes the template's restart.
+fn split_commands(image: &MemoryImage, template: &CommandTemplate, max_length: usize, unit: LengthUnit, optimize: bool, alphabet: &Alphabet) -> Result<Vec<MemoryChunk>, String> {
+    let segments: Vec<(SectionKind, u16, Vec<Option<Word>>)> = [
+        (SectionKind::Memory, 0, image.section(SectionKind::Memory)),
+        (SectionKind::Code, CODE_ADDRESS, image.section(SectionKind::Code).tail(CODE_ADDRESS)),
+    ].into_iter().filter(|(_, _, segment)| !segment.is_empty()).map(|(kind, base, segment)| (kind, base, segment.to_words())).collect();
+
+    let mut chunks = Vec::new();
+    for (i, (kind, base, words)) in segments.iter().enumerate() {
+        let last_segment = i == segments.len() - 1;
+        // Only the first message clears, wherever in memory it starts.
+        let chunk_at = |start: usize, len: usize, clear: bool| {
+            let image = MemoryImage::from_words(&words[start..start + len]);
+            let groups = construct(&mut WordGroupConstructor::from_image(&image), optimize).unwrap_or_default();
+            let chunk = MemoryChunk { kind: *kind, words: groups, offset: start as u16, image, clear };
+            let mut command = chunk.command(template, words_to_string(chunk.words.clone(), false, alphabet)?);
+            if last_segment && start + len == words.len() {
+                command += &template.restart_suffix();
+            }
+            Ok::<_, String>((unit.measure(&command) <= max_length, chunk))
+        };
 
-    let version = &bytes[4];
-
-    match version {
-        0 | 1 => {
-            let mem_start = bytes[5] as usize;
-            let code_start = bytes[6] as usize;
-
-            let mem_size = match (mem_start, code_start) {
-                (0, _) => 0,
-                (_, 0) => bytes.len() - mem_start,
-                (_, _) => code_start - mem_start
-            };
-            let code_size = match (mem_start, code_start) {
-                (_, 0) => 0,
-                (_, _) => bytes.len() - code_start
-            };
-
-            file.header = bytes[0..7].to_vec();
-
-            file.memory = bytes[mem_start..mem_start + mem_size].to_vec();
-            file.code = bytes[code_start..code_start + code_size].to_vec();
-            file.memory_start = mem_start;
-            file.code_start = code_start;
-        },
-        2 => {
-            todo!();
-        },
-        _ => return Err(format!("Unsupported version {version}.")),
+        let mut start = 0;
+        while start < words.len() {
+            // Unwritten words between commands are skipped over by the next offset.
+            if words[start].is_none() {
+                start += 1;
+                continue;
+            }
+            let clear = chunks.is_empty();
+            let (fits, mut chunk) = chunk_at(start, 1, clear)?;
+            if !fits {
+                return Err(format!("The word at {:04x} doesn't fit in a {max_length} {} message.", start + *base as usize, unit.name()));
+            }
+            // Longer runs take more to write, so search for the longest that fits.
+            let (mut lo, mut hi) = (1, words.len() - start);
+            while lo < hi {
+                let mid = (lo + hi).div_ceil(2);
+                match chunk_at(start, mid, clear)? {
+                    (true, fitting) => (lo, chunk) = (mid, fitting),
+                    (false, _) => hi = mid - 1,
+                }
+            }
+            start += lo;
+            chunks.push(chunk);
+        }
     }
-
-    Ok(file)
+    Ok(chunks)
 }
 
 #[test]
-fn test_bin_file_loader() {
-    let bytes = b"MWvm\x01\x00\x00";
-    let file = parse_binary_file(bytes.to_vec()).unwrap();
-    assert_eq!(file.header, b"MWvm\x01\x00\x00");
-    assert_eq!(file.memory, b"");
-    assert_eq!(file.code, b"");
-
-    let bytes = b"MWvm\x01\x07\x08\x01\x02";
-    let file = parse_binary_file(bytes.to_vec()).unwrap();
-    assert_eq!(file.header, b"MWvm\x01\x07\x08");
-    assert_eq!(file.memory, b"\x01");
-    assert_eq!(file.code, b"\x02");
-
-    let bytes = b"MWvm\x01\x07\x0b12345678";
-    let file = parse_binary_file(bytes.to_vec()).unwrap();
-    assert_eq!(file.header, b"MWvm\x01\x07\x0b");
-    assert_eq!(file.memory, b"1234");
-    assert_eq!(file.code, b"5678");
-}
-
-fn hex_to_words(hex_string: String) -> Vec<Word> {
-    let mut words = Vec::new();
-
-    let hex_string = hex_string.trim().replace(" ", "").replace("\n", "");
+fn test_split_commands() {
+    let vm = CommandTemplate::vm();
+    let words: Vec<Option<Word>> = (0..100).map(|i| Some(Word::new(0x1111 * (i % 15 + 1)))).collect();
+    let image = MemoryImage::from_words(&words);
+
+    // Renders every chunk the way main does, checking it fits.
+    let assert_fits = |chunks: &[MemoryChunk], max_length: usize, unit: LengthUnit| {
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.clear, i == 0);
+            let mut command = chunk.command(&vm, words_to_string(chunk.words.clone(), false, Alphabet::runes()).unwrap());
+            if i == chunks.len() - 1 { command += &vm.restart_suffix(); }
+            assert!(unit.measure(&command) <= max_length, "{command}");
+        }
+    };
 
-    for i in (0..hex_string.len()).step_by(4) {
-        let word_str = &hex_string[i..(i + 4).min(hex_string.len())];
-        let word = Word::new(u16::from_str_radix(word_str, 16).unwrap());
-        words.push(word);
+    for (max_length, unit) in [(60, LengthUnit::Chars), (120, LengthUnit::Bytes), (500, LengthUnit::Chars)] {
+        let chunks = split_commands(&image, &vm, max_length, unit, false, Alphabet::runes()).unwrap();
+        assert_fits(&chunks, max_length, unit);
+        let mut covered = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk.offset as usize, covered);
+            covered += chunk.image.len();
+        }
+        assert_eq!(covered, 100);
     }
 
-    words
-}
-
-#[test]
-fn test_hex_to_words() {
-    let hex_string = "12345678".to_string();
-    let words = hex_to_words(hex_string);
-    assert_eq!(words.len(), 2);
-    assert_eq!(words[0].value(), 0x1234);
-    assert_eq!(words[1].value(), 0x5678);
-}
-
-fn binary_to_words(input: Vec<u8>) -> Vec<Word> {
-    let mut words = Vec::new();
+    // The first message clears even when it starts past the first word.
+    let image = MemoryImage::from_words(&[vec![None; 0x10], words.clone()].concat());
+    let chunks = split_commands(&image, &vm, 60, LengthUnit::Chars, false, Alphabet::runes()).unwrap();
+    assert_eq!(chunks[0].offset, 0x10);
+    assert_fits(&chunks, 60, LengthUnit::Chars);
 
-    for chunk in input.chunks_exact(2) {
-        let word = Word::new(u16::from_be_bytes([chunk[0], chunk[1]]));
-        words.push(word);
+    // Runs of zeroes pack into a single command, with gaps skipped over.
+    let mut image = MemoryImage::new();
+    for addr in 0..0x80 {
+        image.set(addr, Word::new(0));
     }
+    image.set(0x400, Word::new(0xdead));
+    let chunks = split_commands(&image, &vm, 30, LengthUnit::Chars, false, Alphabet::runes()).unwrap();
+    assert_eq!(chunks.iter().map(|chunk| chunk.offset).collect::<Vec<_>>(), vec![0, 0x400]);
+
+    // Code keeps its own verb and offsets from the start of the code segment.
+    let mut image = MemoryImage::new();
+    image.add_region("code", SectionKind::Code, CODE_ADDRESS, 0x100);
+    for addr in 0..0x60 {
+        image.set(addr, Word::new(0x1111 * (addr % 15 + 1)));
+    }
+    let chunks = split_commands(&image, &vm, 120, LengthUnit::Chars, false, Alphabet::runes()).unwrap();
+    let layout: Vec<(SectionKind, u16, usize)> = chunks.iter().map(|chunk| (chunk.kind, chunk.offset, chunk.image.len())).collect();
+    assert_eq!(layout, vec![
+        (SectionKind::Memory, 0, 26), (SectionKind::Memory, 26, 26), (SectionKind::Memory, 52, 12),
+        (SectionKind::Code, 0, 27), (SectionKind::Code, 27, 5),
+    ]);
+    assert!(chunks[4].command(&vm, String::new()).starts_with("!vm code ᚾᛗᚢ"));
+    assert_fits(&chunks, 120, LengthUnit::Chars);
+
+    // Without any memory the first message, which clears, is a code one.
+    let mut code_only = MemoryImage::new();
+    code_only.add_region("code", SectionKind::Code, CODE_ADDRESS, 0x100);
+    for addr in 0..0x40 {
+        code_only.set(CODE_ADDRESS + addr, Word::new(0x1111 * (addr % 15 + 1)));
+    }
+    let chunks = split_commands(&code_only, &vm, 60, LengthUnit::Chars, false, Alphabet::runes()).unwrap();
+    assert!(chunks.iter().all(|chunk| chunk.kind == SectionKind::Code));
+    assert!(chunks[0].command(&vm, String::new()).starts_with("!vm clear code"));
+    assert_fits(&chunks, 60, LengthUnit::Chars);
 
-    words
+    assert_eq!(split_commands(&image, &vm, 10, LengthUnit::Chars, false, Alphabet::runes()).err().unwrap(), "The word at 0000 doesn't fit in a 10 character message.");
 }
 
-#[test]
-fn test_binary_to_words() {
-    let input = vec![0x12, 0x34, 0x56, 0x78];
-    let words = binary_to_words(input);
-    assert_eq!(words.len(), 2);
-    assert_eq!(words[0].value(), 0x1234);
-    assert_eq!(words[1].value(), 0x5678);
-}
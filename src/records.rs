@@ -0,0 +1,255 @@
+use crate::{image::MemoryImage, word::Word};
+
+/// Byte addresses in the VM's 0x1000 word address space.
+const BYTE_SPACE: usize = 0x2000;
+
+/// Data bytes written per record.
+const RECORD_LEN: usize = 16;
+
+/// Places bytes at their byte addresses, erroring if any fall outside memory.
+fn store(bytes: &mut Vec<Option<u8>>, address: usize, data: &[u8], line_no: usize) -> Result<(), String> {
+    if address + data.len() > BYTE_SPACE {
+        return Err(format!("Line {line_no}: data at {address:#06x} runs past the end of memory."));
+    }
+    if bytes.len() < address + data.len() {
+        bytes.resize(address + data.len(), None);
+    }
+    for (i, byte) in data.iter().enumerate() {
+        bytes[address + i] = Some(*byte);
+    }
+    Ok(())
+}
+
+/// Pairs bytes up into big-endian words, word N being bytes 2N and 2N+1. A
+/// word with only one of its bytes set gets zero for the other.
+fn bytes_to_image(bytes: &[Option<u8>], entry: Option<u16>) -> MemoryImage {
+    let mut image = MemoryImage::new();
+    image.entry = entry;
+    for (addr, pair) in bytes.chunks(2).enumerate() {
+        match (pair[0], pair.get(1).copied().flatten()) {
+            (None, None) => {}
+            (high, low) => image.set(addr as u16, Word::new(u16::from_be_bytes([high.unwrap_or(0), low.unwrap_or(0)]))),
+        }
+    }
+    image
+}
+
+/// Runs of written words as `(byte address, bytes)`, cut into record sized pieces.
+fn records(image: &MemoryImage) -> Vec<(usize, Vec<u8>)> {
+    let mut out = Vec::new();
+    let words = image.to_words();
+    let mut i = 0;
+    while i < words.len() {
+        let len = words[i..].iter().take_while(|w| w.is_some()).count();
+        if len == 0 {
+            i += 1;
+            continue;
+        }
+        let bytes: Vec<u8> = words[i..i + len].iter().flat_map(|w| w.unwrap().value().to_be_bytes()).collect();
+        for (n, chunk) in bytes.chunks(RECORD_LEN).enumerate() {
+            out.push((i * 2 + n * RECORD_LEN, chunk.to_vec()));
+        }
+        i += len;
+    }
+    out
+}
+
+/// Decodes a record's hex digits and checks its trailing checksum, which
+/// brings the byte sum to `sum_target` modulo 256.
+fn record_bytes(digits: &str, sum_target: u8, line_no: usize) -> Result<Vec<u8>, String> {
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Line {line_no}: record is not a whole number of hex bytes."));
+    }
+    let bytes: Vec<u8> = (0..digits.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect();
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    if bytes.len() < 2 || sum != sum_target {
+        return Err(format!("Line {line_no}: bad checksum."));
+    }
+    if bytes[0] as usize != bytes.len() - if sum_target == 0 { 5 } else { 1 } {
+        return Err(format!("Line {line_no}: record length doesn't match its data."));
+    }
+    Ok(bytes)
+}
+
+fn hex_digits(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Reads an Intel HEX file into an image, with its start address as the entry
+/// point. Byte addresses map onto words two bytes each, high byte first.
+pub fn ihex_to_image(text: &str) -> Result<MemoryImage, String> {
+    let mut bytes = Vec::new();
+    let mut base = 0;
+    let mut entry = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(digits) = line.strip_prefix(':') else {
+            return Err(format!("Line {line_no}: expected a record starting with `:`."));
+        };
+        if digits.len() < 10 {
+            return Err(format!("Line {line_no}: record too short."));
+        }
+        let record = record_bytes(digits, 0, line_no)?;
+        let address = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let data = &record[4..record.len() - 1];
+
+        match (record[3], data.len()) {
+            (0x00, _) => store(&mut bytes, base + address, data, line_no)?,
+            (0x01, _) => break,
+            (0x02, 2) => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 4,
+            (0x04, 2) => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 16,
+            (0x03, 4) => {
+                let segment = u16::from_be_bytes([data[0], data[1]]) as usize;
+                let offset = u16::from_be_bytes([data[2], data[3]]) as usize;
+                entry = Some(segment * 16 + offset);
+            }
+            (0x05, 4) => entry = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize),
+            (kind @ (0x02..=0x05), _) => return Err(format!("Line {line_no}: record type {kind:02x} has the wrong length.")),
+            (kind, _) => return Err(format!("Line {line_no}: unknown record type {kind:02x}.")),
+        }
+    }
+
+    let entry = match entry {
+        Some(entry) if entry >= BYTE_SPACE || !entry.is_multiple_of(2) => {
+            return Err(format!("Start address {entry:#06x} is not a word address in memory."));
+        }
+        entry => entry.map(|entry| (entry / 2) as u16),
+    };
+    Ok(bytes_to_image(&bytes, entry))
+}
+
+/// Writes an image as Intel HEX, with a start address record for the entry point.
+pub fn image_to_ihex(image: &MemoryImage) -> String {
+    let line = |kind: u8, address: u16, data: &[u8]| {
+        let mut record = vec![data.len() as u8];
+        record.extend(address.to_be_bytes());
+        record.push(kind);
+        record.extend(data);
+        let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        record.push(sum.wrapping_neg());
+        format!(":{}\n", hex_digits(&record))
+    };
+
+    let mut out = String::new();
+    for (address, data) in records(image) {
+        out += &line(0x00, address as u16, &data);
+    }
+    if let Some(entry) = image.entry {
+        out += &line(0x05, 0, &(entry as u32 * 2).to_be_bytes());
+    }
+    out += &line(0x01, 0, &[]);
+    out
+}
+
+#[test]
+fn test_ihex() {
+    let text = ":0400000012345678E8\n:02000600BEEF4B\n:0400000500000010E7\n:00000001FF\n";
+    let image = ihex_to_image(text).unwrap();
+    assert_eq!(image.to_words(), vec![
+        Some(Word::new(0x1234)), Some(Word::new(0x5678)), None,
+        Some(Word::new(0xbeef)),
+    ]);
+    assert_eq!(image.entry, Some(8));
+    assert_eq!(image_to_ihex(&image), text);
+
+    // Extended addresses and odd bytes.
+    let image = ihex_to_image(":020000020100FB\n:010003007F7D\n").unwrap();
+    assert_eq!(image.len(), 0x802);
+    assert_eq!(image.get(0x801), Some(Word::new(0x007f)));
+
+    assert_eq!(ihex_to_image(":0400000012345678E9").unwrap_err(), "Line 1: bad checksum.");
+    assert_eq!(ihex_to_image("\n0400000012345678E8").unwrap_err(), "Line 2: expected a record starting with `:`.");
+    assert_eq!(ihex_to_image(":020000040001F9\n:0100000000FF").unwrap_err(), "Line 2: data at 0x10000 runs past the end of memory.");
+}
+
+/// Reads a Motorola S-record file into an image, with its start address as
+/// the entry point if it is nonzero. Byte addresses map onto words as for Intel HEX.
+pub fn srec_to_image(text: &str) -> Result<MemoryImage, String> {
+    let mut bytes = Vec::new();
+    let mut entry = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut chars = line.chars();
+        let (Some('S'), Some(kind)) = (chars.next(), chars.next()) else {
+            return Err(format!("Line {line_no}: expected a record starting with `S`."));
+        };
+        let address_len = match kind {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return Err(format!("Line {line_no}: unknown record type S{kind}.")),
+        };
+        let record = record_bytes(chars.as_str(), 0xff, line_no)?;
+        if record.len() < address_len + 2 {
+            return Err(format!("Line {line_no}: record too short."));
+        }
+        let address = record[1..=address_len].iter().fold(0usize, |a, b| a << 8 | *b as usize);
+        let data = &record[address_len + 1..record.len() - 1];
+
+        match kind {
+            '1' | '2' | '3' => store(&mut bytes, address, data, line_no)?,
+            '7' | '8' | '9' if address != 0 => entry = Some(address),
+            _ => {}
+        }
+    }
+
+    let entry = match entry {
+        Some(entry) if entry >= BYTE_SPACE || !entry.is_multiple_of(2) => {
+            return Err(format!("Start address {entry:#06x} is not a word address in memory."));
+        }
+        entry => entry.map(|entry| (entry / 2) as u16),
+    };
+    Ok(bytes_to_image(&bytes, entry))
+}
+
+/// Writes an image as S1 records with a record count and an S9 start address.
+pub fn image_to_srec(image: &MemoryImage) -> String {
+    let line = |kind: char, address: u16, data: &[u8]| {
+        let mut record = vec![data.len() as u8 + 3];
+        record.extend(address.to_be_bytes());
+        record.extend(data);
+        let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        record.push(!sum);
+        format!("S{kind}{}\n", hex_digits(&record))
+    };
+
+    let records = records(image);
+    let mut out = line('0', 0, &[]);
+    for (address, data) in &records {
+        out += &line('1', *address as u16, data);
+    }
+    out += &line('5', records.len() as u16, &[]);
+    out += &line('9', image.entry.map_or(0, |entry| entry * 2), &[]);
+    out
+}
+
+#[test]
+fn test_srec() {
+    let text = "S0030000FC\nS107000012345678E4\nS1050006BEEF47\nS5030002FA\nS9030010EC\n";
+    let image = srec_to_image(text).unwrap();
+    assert_eq!(image.to_words(), vec![
+        Some(Word::new(0x1234)), Some(Word::new(0x5678)), None,
+        Some(Word::new(0xbeef)),
+    ]);
+    assert_eq!(image.entry, Some(8));
+    assert_eq!(image_to_srec(&image), text);
+
+    let image = srec_to_image("S20800100012345678D3\nS804000000FB\n").unwrap();
+    assert_eq!(image.get(0x801), Some(Word::new(0x5678)));
+    assert_eq!(image.entry, None);
+
+    assert_eq!(srec_to_image("S107000012345678E5").unwrap_err(), "Line 1: bad checksum.");
+    assert_eq!(srec_to_image("S4030000FC").unwrap_err(), "Line 1: unknown record type S4.");
+}
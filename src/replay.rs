@@ -0,0 +1,128 @@
+use crate::{alphabet::Alphabet, binary::CODE_ADDRESS, decoder::Decoder, image::{MemoryImage, MEMORY_SIZE}, template::CommandTemplate, word::Word};
+
+/// Replays a log of chat commands into the memory they leave the VM with,
+/// along with warnings for messages that were skipped.
+///
+/// Each line holding the template's prefix is a message, and anything before
+/// the prefix, like a timestamp or username, is ignored along with lines
+/// without one. `write` and `code` decode their runes from the start of memory
+/// and code, and `clear` writes zero to every word like the VM does. Commands
+/// the template leaves out, like `vm-keep`'s clear, are read as `vm`'s, since
+/// the same bot still runs them. Messages with a command the bot doesn't know
+/// are skipped whole. Replaying stops after line `until`, if given.
+pub fn replay(log: &str, template: &CommandTemplate, alphabet: &Alphabet, until: Option<usize>) -> (MemoryImage, Vec<String>) {
+    let vm = CommandTemplate::vm();
+    let or_vm = |word: &String, vm_word: String| if word.is_empty() { vm_word } else { word.clone() };
+    let clear = or_vm(&template.clear, vm.clear);
+    let write = or_vm(&template.write, vm.write);
+    let code = or_vm(&template.code, vm.code);
+    let reset = or_vm(&template.reset, vm.reset);
+    let restart = or_vm(&template.restart, vm.restart);
+
+    let mut image = MemoryImage::new();
+    let mut warnings = Vec::new();
+
+    for (i, line) in log.lines().enumerate() {
+        let line_no = i + 1;
+        if until.is_some_and(|until| line_no > until) {
+            break;
+        }
+        let mut tokens = line.split_whitespace().skip_while(|token| *token != template.prefix).skip(1).peekable();
+
+        // Writes run up to the next separator, or the next command without one.
+        // `None` clears memory.
+        let mut commands = Vec::new();
+        let mut unknown = None;
+        while let Some(verb) = tokens.next() {
+            let base = match verb {
+                _ if !template.separator.is_empty() && verb == template.separator => continue,
+                _ if verb == clear => {
+                    commands.push(None);
+                    continue;
+                }
+                _ if verb == reset || verb == restart => continue,
+                _ if verb == write => 0,
+                _ if verb == code => CODE_ADDRESS,
+                _ => {
+                    unknown = Some(verb);
+                    break;
+                }
+            };
+
+            let mut runes = String::new();
+            while let Some(token) = tokens.next_if(|token| match template.separator.as_str() {
+                "" => ![&clear, &write, &code, &reset, &restart].iter().any(|verb| verb == token),
+                separator => *token != separator,
+            }) {
+                runes += token;
+            }
+            commands.push(Some((base, runes)));
+        }
+        if let Some(verb) = unknown {
+            warnings.push(format!("Line {line_no}: unknown command `{verb}`, skipping the message."));
+            continue;
+        }
+
+        for command in commands {
+            let Some((base, runes)) = command else {
+                for addr in 0..MEMORY_SIZE as u16 {
+                    image.set(addr, Word::new(0));
+                }
+                continue;
+            };
+            for event in Decoder::new(&alphabet.to_runes(&runes)) {
+                for (addr, value) in event.writes() {
+                    image.set(base.wrapping_add(addr), value.into());
+                }
+            }
+        }
+    }
+
+    (image, warnings)
+}
+
+#[test]
+fn test_replay() {
+    let vm = CommandTemplate::vm();
+    let runes = Alphabet::runes();
+    let log = "\
+[12:00:01] alice: !vm clear write ᛜᛞᛖᛜᛗᛞᛞᛟ ! code ᚾᛁᛃᛈ ! reset
+[12:00:05] bob: nice
+[12:01:00] alice: !vm write ᚾᚢᛚᛖᛟᛞ ! restart
+[12:02:00] alice: !vm clear code ᛇᛇᛇᛇ
+";
+
+    let (image, _) = replay(log, &vm, runes, None);
+    let mut expected = vec![Some(Word::new(0)); MEMORY_SIZE];
+    expected[0x40] = Some(Word::new(0x5555));
+    assert_eq!(image.to_words(), expected);
+
+    let (image, _) = replay(log, &vm, runes, Some(3));
+    assert_eq!(image.get(0), Some(Word::new(0xdead)));
+    assert_eq!(image.get(1), Some(Word::new(0xcafe)));
+    assert_eq!(image.get(2), Some(Word::new(0)));
+    assert_eq!(image.get(0x40), Some(Word::new(0x1234)));
+
+    // Templates without a clear still read the bot's clear.
+    let keep = CommandTemplate::from_arg("vm-keep").unwrap();
+    let (image, _) = replay("!vm write ᚾᚢᛚᛖᛟᛞ ! restart", &keep, runes, None);
+    assert_eq!(image.to_words(), vec![None, Some(Word::new(0xcafe))]);
+    let (image, _) = replay("!vm clear write ᚾᚢᛚᛖᛟᛞ ! restart", &keep, runes, None);
+    assert_eq!(image.len(), MEMORY_SIZE);
+    assert_eq!(image.get(1), Some(Word::new(0xcafe)));
+
+    // Templates without a separator split commands at their verbs.
+    let plain = CommandTemplate::parse("prefix = vm\nseparator =").unwrap();
+    let (image, _) = replay("vm clear write dead code 1234 reset", &plain, &Alphabet::ascii(), None);
+    assert_eq!(image.get(0), Some(Word::new(0xdead)));
+    assert_eq!(image.get(0x40), Some(Word::new(0x1234)));
+    let plain_keep = CommandTemplate::parse("prefix = vm\nseparator =\nclear =").unwrap();
+    let (image, _) = replay("vm write dead code 1234 reset", &plain_keep, &Alphabet::ascii(), None);
+    assert_eq!(image.len(), 0x41);
+
+    // Messages with an unknown command are skipped with a warning.
+    let (image, warnings) = replay("!vm write ᛜᛞᛖᛜ ! help\n!vm code ᚾᛁᛃᛈ", &vm, runes, None);
+    assert_eq!(image.get(0), None);
+    assert_eq!(image.get(0x40), Some(Word::new(0x1234)));
+    assert_eq!(warnings, ["Line 1: unknown command `help`, skipping the message."]);
+}
@@ -1,132 +1,63 @@
-use crate::word::Word;
+use crate::{decoder::Decoder, image::{MemoryImage, MEMORY_SIZE}};
 
+/// Decodes runes into the image they write, leaving addresses they never touch unwritten.
+pub fn reverse_write(omnom: String) -> MemoryImage {
+    reverse_write_counted(omnom).0
+}
 
-pub fn reverse_write(omnom: String) -> Vec<Word> {
-    let mut val: u16 = 0;
-    let mut addr: u16 = 0;
-    let order = [12u32,8,4,0];
-    let unorder = [0, 12u32,8,4,0];
-    let mut ofs_index = 0u32;
-    let mut last_written = 0u16;
-    let mut bytes: [u16; 0x1000] = [0; 0x1000];
-    let mut high: u16 = 0;
+/// Decodes runes like `reverse_write`, also counting how many times each address is written.
+pub fn reverse_write_counted(omnom: String) -> (MemoryImage, Vec<u32>) {
+    let mut image = MemoryImage::new();
+    let mut counts = vec![0; MEMORY_SIZE];
 
-    for bite in omnom.chars() {
-        if let Some(hex_val) = match bite {
-            '0'..='9' => Some((bite as u8 - b'0') as u16),
-            // abcdef
-            'A'..='F' => Some((bite as u8 - b'A' + 10) as u16),
-            'a'..='f' => Some((bite as u8 - b'a' + 10) as u16),
-            // uvwxyz
-            'U'..='Z' => Some((bite as u8 - b'U' + 10) as u16),
-            'u'..='z' => Some((bite as u8 - b'u' + 10) as u16),
-            // skip forward in the address space
-            // by N
-            'ᚢ' => {
-                let ofs = unorder[ofs_index as usize];
-                val >>= ofs;
-                if val == 0 { val = 1; }
-                addr = addr.wrapping_add(val) % 0x1000;
-                ofs_index = 0;
-                val = 0;
-                None
-            }
-            // skip forward a word without writting
-            // and without affecting input
-            'ᚨ' => {
-                addr = addr.wrapping_add(1);
-                None
-            }
-            // write 0 words, N times
-            'ᚠ' => {
-                let ofs = unorder[ofs_index as usize];
-                val >>= ofs;
-                if val == 0 { val = 1; }
-                while val > 0 {
-                    // vmproc.write_priv(addr, 0);
-                    bytes[addr as usize] = 0;
-                    high = high.max(addr);
-                    addr = addr.wrapping_add(1) % 0x1000;
-                    val -= 1;
-                }
-                ofs_index = 0;
-                val = 0;
-                None
-            }
-            // repeat the "last written" value 1 or N times
-            'ᚱ' => {
-                let ofs = unorder[ofs_index as usize];
-                val >>= ofs;
-                if val == 0 { val = 1; }
-                while val > 0 {
-                    // vmproc.write_priv(addr, last_written);
-                    bytes[addr as usize] = last_written;
-                    high = high.max(addr);
-                    addr = addr.wrapping_add(1) % 0x1000;
-                    val -= 1;
-                }
-                ofs_index = 0;
-                val = 0;
-                None
-            }
-            // right align and write current value
-            '×' => {
-                let ofs = unorder[ofs_index as usize];
-                val >>= ofs;
-                last_written = val;
-                // vmproc.write_priv(addr, val);
-                bytes[addr as usize] = last_written;
-                high = high.max(addr);
-                addr = addr.wrapping_add(1) % 0x1000;
-                ofs_index = 0;
-                val = 0;
-                None
-            },
-            // left align and write current value
-            'ᚲ' => {
-                last_written = val;
-                // vmproc.write_priv(addr, val);
-                bytes[addr as usize] = last_written;
-                high = high.max(addr);
-                addr = addr.wrapping_add(1) % 0x1000;
-                ofs_index = 0;
-                val = 0;
-                None
-            }
-            // alternate hex data, 0-F equiv
-            // ᚺᚾ ᛁᛃ ᛈᛇ ᛉᛊ ᛏᛒ ᛖᛗ ᛚᛜ ᛞᛟ
-            'ᚺ' => Some(0), 'ᚾ' => Some(1), 'ᛁ' => Some(2), 'ᛃ' => Some(3),
-            'ᛈ' => Some(4), 'ᛇ' => Some(5), 'ᛉ' => Some(6), 'ᛊ' => Some(7),
-            'ᛏ' => Some(8), 'ᛒ' => Some(9), 'ᛖ' => Some(10), 'ᛗ' => Some(11),
-            'ᛚ' => Some(12), 'ᛜ' => Some(13), 'ᛞ' => Some(14), 'ᛟ' => Some(15),
-            _ => None
-        } {
-            let ofs = order[ofs_index as usize];
-            val |= hex_val << ofs;
-            ofs_index += 1;
-        }
-        if ofs_index >= 4 {
-            last_written = val;
-            // vmproc.write_priv(addr, val);
-            bytes[addr as usize] = last_written;
-            high = high.max(addr);
-            val = 0;
-            addr += 1;
-            ofs_index = 0;
+    for event in Decoder::new(&omnom) {
+        for (addr, value) in event.writes() {
+            // vmproc.write_priv(addr, value);
+            image.set(addr, value.into());
+            counts[addr as usize] += 1;
         }
     }
-    if ofs_index > 0 {
-        // vmproc.write_priv(addr, val);
-        bytes[addr as usize] = val;
-        high = high.max(addr);
-    }
 
-    // println!("{:?}", bytes);
+    (image, counts)
+}
 
-    // Return bytes[0..high] as a Vector
-    let mut result = Vec::new();
-    for i in 0..=high {
-        result.push(Word::from(bytes[i as usize]));
+/// Lists the addresses runes write, with runs that are written the same
+/// number of times on one line. Anything not listed is left untouched.
+pub fn written_listing(counts: &[u32]) -> String {
+    let mut out = "address    writes".to_string();
+    let mut start = 0;
+    while start < counts.len() {
+        let count = counts[start];
+        let len = counts[start..].iter().take_while(|&&c| c == count).count();
+        if count > 0 {
+            let range = if len == 1 { format!("{:04x}", start) } else { format!("{:04x}-{:04x}", start, start + len - 1) };
+            out += &format!("\n{:<9}  {}", range, count);
+        }
+        start += len;
     }
-    result
+    out
+}
+
+#[test]
+fn test_reverse_write() {
+    use crate::word::Word;
+
+    let image = reverse_write("ᛜᛞᛖᛜᚢᛈᛁ×".to_string());
+    assert_eq!(image.to_words(), vec![Some(Word::new(0xdead)), None, Some(Word::new(0x42))]);
+    assert_eq!(image.dense(), vec![Word::new(0xdead), Word::new(0), Word::new(0x42)]);
+
+    // Skipping alone writes nothing at all.
+    assert!(reverse_write("ᛃᚢ".to_string()).is_empty());
+}
+
+#[test]
+fn test_written_listing() {
+    // Write 3 words, skip back round to 1 and zero it again.
+    let (image, counts) = reverse_write_counted("ᛃᚠᛟᛟᛞᚢᚠ".to_string());
+    assert_eq!(image.len(), 3);
+    assert_eq!(&counts[..4], &[1, 2, 1, 0]);
+    assert_eq!(written_listing(&counts), "address    writes\n0000       1\n0001       2\n0002       1");
+
+    let (_, counts) = reverse_write_counted("ᛟᛟᚠᛃᚢᚠ".to_string());
+    assert_eq!(written_listing(&counts), "address    writes\n0000-00fe  1\n0102       1");
 }
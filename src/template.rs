@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use crate::definition::{load_definition, parse_definition, unquote};
+
+/// The stream VM chat bot's commands. Each line names a part of a command
+/// and the word written for it, where an empty word leaves that part out.
+const VM: &str = "\
+# The stream VM chat bot
+prefix = !vm
+clear = clear
+write = write
+code = code
+separator = !
+reset = reset
+restart = restart
+";
+
+/// The same bot, patching memory without clearing it first.
+const VM_KEEP: &str = "\
+# The stream VM chat bot, leaving memory that isn't written intact
+clear =
+";
+
+/// The words chat commands are built from, so the same image can be sent to
+/// bots that spell their commands differently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandTemplate {
+    /// Starts every message.
+    pub prefix: String,
+    /// Follows the prefix in the first message, to clear the VM first.
+    pub clear: String,
+    /// Writes memory from address 0.
+    pub write: String,
+    /// Writes code from the start of the code segment.
+    pub code: String,
+    /// Goes between commands in the same message.
+    pub separator: String,
+    /// Ends a single message, to run what it wrote.
+    pub reset: String,
+    /// Ends the last of several messages, to run what they wrote.
+    pub restart: String,
+}
+
+impl Default for CommandTemplate {
+    fn default() -> Self {
+        CommandTemplate::vm()
+    }
+}
+
+impl CommandTemplate {
+    /// The stream VM chat bot's commands.
+    pub fn vm() -> CommandTemplate {
+        CommandTemplate::parse(VM).unwrap()
+    }
+
+    /// Parses a template, one `part = word` per line.
+    ///
+    /// Parts are `prefix`, `clear`, `write`, `code`, `separator`, `reset` and
+    /// `restart`. Parts that aren't given are the same as for `vm`, and an
+    /// empty word leaves a part out. Blank lines and `#` comments are ignored,
+    /// and words may be quoted.
+    pub fn parse(definition: &str) -> Result<CommandTemplate, String> {
+        let mut template = CommandTemplate {
+            prefix: "!vm".to_string(),
+            clear: "clear".to_string(),
+            write: "write".to_string(),
+            code: "code".to_string(),
+            separator: "!".to_string(),
+            reset: "reset".to_string(),
+            restart: "restart".to_string(),
+        };
+
+        parse_definition(definition, "part = word", |name, word| {
+            let part = match name {
+                "prefix" => &mut template.prefix,
+                "clear" => &mut template.clear,
+                "write" => &mut template.write,
+                "code" => &mut template.code,
+                "separator" => &mut template.separator,
+                "reset" => &mut template.reset,
+                "restart" => &mut template.restart,
+                _ => return Err(format!("unknown part `{name}`.")),
+            };
+            *part = unquote(word).to_string();
+            Ok(())
+        })?;
+
+        if template.prefix.is_empty() || template.write.is_empty() || template.code.is_empty() {
+            return Err("Commands need a `prefix`, `write` and `code`.".to_string());
+        }
+        Ok(template)
+    }
+
+    pub fn load(path: &Path) -> Result<CommandTemplate, String> {
+        load_definition(path, CommandTemplate::parse)
+    }
+
+    /// Parses a command line template: `vm`, `vm-keep` or a template file.
+    pub fn from_arg(arg: &str) -> Result<CommandTemplate, String> {
+        match arg {
+            "vm" => Ok(CommandTemplate::vm()),
+            "vm-keep" => CommandTemplate::parse(VM_KEEP),
+            path => CommandTemplate::load(Path::new(path)),
+        }
+    }
+
+    /// Joins commands into one message, with the clear command if `clear`.
+    pub fn message(&self, clear: bool, commands: &[String]) -> String {
+        let head = if clear && !self.clear.is_empty() { format!("{} {}", self.prefix, self.clear) } else { self.prefix.clone() };
+        let separator = if self.separator.is_empty() { " ".to_string() } else { format!(" {} ", self.separator) };
+        format!("{head} {}", commands.join(&separator))
+    }
+
+    /// What ends the last of several messages.
+    pub fn restart_suffix(&self) -> String {
+        match (self.restart.is_empty(), self.separator.is_empty()) {
+            (true, _) => String::new(),
+            (false, true) => format!(" {}", self.restart),
+            (false, false) => format!(" {} {}", self.separator, self.restart),
+        }
+    }
+}
+
+#[test]
+fn test_command_template() {
+    let vm = CommandTemplate::vm();
+    assert_eq!(vm.message(true, &["write 1234".to_string(), "reset".to_string()]), "!vm clear write 1234 ! reset");
+    assert_eq!(vm.message(false, &["code 5678".to_string()]), "!vm code 5678");
+    assert_eq!(vm.restart_suffix(), " ! restart");
+
+    let keep = CommandTemplate::from_arg("vm-keep").unwrap();
+    assert_eq!(keep.message(true, &["write 1234".to_string()]), "!vm write 1234");
+
+    let test = CommandTemplate::parse("# test instance\nprefix = \"!vmtest\"\nseparator = ;\nrestart =\n").unwrap();
+    assert_eq!(test.message(true, &["write 1234".to_string(), "reset".to_string()]), "!vmtest clear write 1234 ; reset");
+    assert_eq!(test.restart_suffix(), "");
+
+    assert_eq!(CommandTemplate::parse("prefix = !a\nprefix = !b").unwrap_err(), "Line 2: `prefix` is defined twice.");
+    assert_eq!(CommandTemplate::parse("verb = x").unwrap_err(), "Line 1: unknown part `verb`.");
+    assert_eq!(CommandTemplate::parse("write =").unwrap_err(), "Commands need a `prefix`, `write` and `code`.");
+}
@@ -1,159 +1,64 @@
 use std::fmt::Display;
 
-use crate::word::Word;
+use crate::{alphabet::{Alphabet, Symbol}, decoder::{Action, Decoder}, image::MemoryImage, reverse::reverse_write, word::Word};
 
 pub fn char_to_rune(c: char) -> Option<char> {
-    match c {
-        '0' => Some('ᚺ'),
-        '1' => Some('ᚾ'),
-        '2' => Some('ᛁ'),
-        '3' => Some('ᛃ'),
-        '4' => Some('ᛈ'),
-        '5' => Some('ᛇ'),
-        '6' => Some('ᛉ'),
-        '7' => Some('ᛊ'),
-        '8' => Some('ᛏ'),
-        '9' => Some('ᛒ'),
-        'a' | 'A' => Some('ᛖ'),
-        'b' | 'B' => Some('ᛗ'),
-        'c' | 'C' => Some('ᛚ'),
-        'd' | 'D' => Some('ᛜ'),
-        'e' | 'E' => Some('ᛞ'),
-        'f' | 'F' => Some('ᛟ'),
-        '*' => Some('ᚱ'),
-        'z' => Some('ᚠ'),
-        '<' => Some('ᚲ'),
-        '>' => Some('×'),
-        '_' => Some('ᚢ'),
-        _=> None,
-    }
+    Symbol::from_notation(c).and_then(|symbol| Alphabet::runes().encode(symbol))
 }
 
-#[allow(unused)]
 pub fn rune_to_char(c: char) -> Option<char> {
-    match c {
-        'ᚺ' => Some('0'),
-        'ᚾ' => Some('1'),
-        'ᛁ' => Some('2'),
-        'ᛃ' => Some('3'),
-        'ᛈ' => Some('4'),
-        'ᛇ' => Some('5'),
-        'ᛉ' => Some('6'),
-        'ᛊ' => Some('7'),
-        'ᛏ' => Some('8'),
-        'ᛒ' => Some('9'),
-        'ᛖ' => Some('a'),
-        'ᛗ' => Some('b'),
-        'ᛚ' => Some('c'),
-        'ᛜ' => Some('d'),
-        'ᛞ' => Some('e'),
-        'ᛟ' => Some('f'),
-        'ᚱ' => Some('*'),
-        'ᚠ' => Some('z'),
-        'ᚲ' => Some('<'),
-        '×' => Some('>'),
-        'ᚢ' => Some('_'),
-        _=> None,
-    }
+    Alphabet::runes().decode(c).map(|symbol| symbol.notation())
 }
 
-pub fn string_to_rune(s: &str) -> String {
+/// Converts hex notation to runes, keeping runes that are already converted
+/// and failing on anything else.
+pub fn string_to_rune(s: &str) -> Result<String, String> {
     let mut output = String::new();
     for c in s.chars() {
-        let rune = char_to_rune(c);
-        match rune {
+        match char_to_rune(c) {
             Some(r) => output.push(r),
-            None => output.push(c),
+            None if rune_to_char(c).is_some() => output.push(c),
+            None => return Err(format!("`{c}` is not hex notation or a rune.")),
         }
-        // output.push(c);
     }
-    output
+    Ok(output)
 }
 
-#[allow(unused)]
-pub fn to_word(s: &str) -> Result::<Word, String> {
-    let mut word = 0;
-    let mut i = 0;
-    for c in s.chars() {
-        if let Some(r) = rune_to_char(c) {
-            match r {
-                '0'..='9' | 'a'..='f' | 'A'..='F' => {
-                    word <<= 4;
-                    word |= r.to_digit(16).unwrap() as u16;
-                    i += 1;
-                }
-                _ => {}
-            }
+/// Deflates a rune string into the hex words it writes, eight to a line, in a
+/// form `hex_to_image` reads back. Words the runes skip over are `----`.
+///
+/// Fails on characters the loader would silently ignore, since those are
+/// usually a sign of a mangled paste.
+pub fn rune_to_string(s: String) -> Result::<String, String> {
+    for event in Decoder::new(&s) {
+        if let Action::Unknown(c) = event.action {
+            return Err(format!("Unknown character '{}' at position {}", c, event.span.start));
         }
     }
-    if i == 4 {
-        Ok(Word::new(word))
-    } else {
-        Err(format!("Invalid word length: {}", i))
-    }
-}
-
-#[allow(unused)]
-pub fn rune_to_string(s: String) -> Result::<String, String> {
-    let words: Vec<[char; 4]> = Vec::new();
-    let mut output = String::new();
-    let mut buffer: [char; 4] = ['0'; 4];
-    let mut i = 0;
-    for c in s.chars() {
-        if let Some(r) = rune_to_char(c) {
-            match r {
-                '0'..='9' | 'a'..='f' | 'A'..='F' => {
-                    buffer[i] = r;
-                    i += 1;
-                    if i == 4 {
-                        for j in 0..4 {
-                            output.push(buffer[j] as char);
-                        }
-                        i = 0;
-                    }
-                }
-                'z' => {
-                    if i == 0 {
-                        output.push_str("0000");
-                    } else {
-                        let num = buffer.iter().take(i).collect::<String>();
-                        if let Ok(num) = u16::from_str_radix(&num, 16) {
-                            for _ in 0..num {
-                                output.push_str("0000");
-                            }
-                        }
-                    }
-                }
-                '*' => {
-                    if i > 0 && words.len() > 0 {
-                        let num = words.last().unwrap().iter().collect::<String>();
-                        let num = Word::from(num);
-                    } else {
 
-                    }
-                }
-                '_' => {
-                    if i == 0 {
-
-                    } else {
+    let words = reverse_write(s).to_words();
+    let lines: Vec<String> = words.chunks(8)
+        .map(|chunk| chunk.iter().map(|word| word.map_or("----".to_string(), |word| word.to_string())).collect::<Vec<_>>().join(" "))
+        .collect();
+    Ok(lines.join("\n"))
+}
 
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-    Ok(output)
+#[test]
+fn test_rune_to_string() {
+    assert_eq!(rune_to_string("ᛈᚠᛟᛟᛟᛟᛃᚱᛜᛞᛖᛜᛗᛞᛞᛟᛚᛖᛟᛞᛟᚺᚺᛜᛈᛁ×ᛁᚠᚾ×".to_string()), Ok(
+        "0000 0000 0000 0000 ffff ffff ffff ffff\ndead beef cafe f00d 0042 0000 0000 0001".to_string()
+    ));
+    assert_eq!(rune_to_string("ᛁᚢᛟ×".to_string()), Ok("---- ---- 000f".to_string()));
+    assert_eq!(rune_to_string("ᛟ×!".to_string()), Err("Unknown character '!' at position 2".to_string()));
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum WordGroup {
-    #[allow(unused)]
     Skip,
-    #[allow(unused)]
     SkipChain(usize),
     Zero,
     ZeroChain(usize),
+    Repeat(usize),
     Word(Word),
     WordChain(Word, usize),
     LowNibble(u16, Option<usize>),
@@ -162,43 +67,144 @@ pub enum WordGroup {
     HighNibble(u16, Option<usize>),
 }
 
-impl Display for WordGroup {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hex = match self {
+impl WordGroup {
+    /// Picks the shortest single-write form for a word, optionally followed by `repeat` copies.
+    pub fn literal(word: Word, repeat: Option<usize>) -> WordGroup {
+        let w = word.value();
+        match (w, repeat) {
+            (0, None) => WordGroup::Zero,
+            (0, Some(count)) => WordGroup::ZeroChain(count + 1),
+            (0x0001..=0x000f, _) => WordGroup::LowNibble(w, repeat),
+            (_, _) if w & 0x0fff == 0 => WordGroup::HighNibble(w, repeat),
+            (0x0010..=0x00ff, _) => WordGroup::LowByte(w, repeat),
+            (_, _) if w & 0x00ff == 0 => WordGroup::HighByte(w, repeat),
+            (_, None) => WordGroup::Word(word),
+            (_, Some(count)) => WordGroup::WordChain(word, count),
+        }
+    }
+
+    /// The group in hex notation, before conversion to runes.
+    pub fn notation(&self) -> String {
+        match self {
             WordGroup::Skip => "_".to_string(),
-            WordGroup::SkipChain(count) => format!("{:x}_", count),
+            WordGroup::SkipChain(count) => chain_notation(*count, '_'),
             WordGroup::Zero => "z".to_string(),
-            WordGroup::ZeroChain(count) => format!("{:x}z", count),
+            WordGroup::ZeroChain(count) => chain_notation(*count, 'z'),
+            WordGroup::Repeat(count) => chain_notation(*count, '*'),
             WordGroup::Word(word) => format!("{:04x}", word.value()),
-            WordGroup::WordChain(word, count) => format!("{:04x}{}*", word.value(), count),
+            WordGroup::WordChain(word, count) => format!("{:04x}{}", word.value(), chain_notation(*count, '*')),
             WordGroup::LowNibble(word, None) => format!("{:01x}>", word),
             WordGroup::LowByte(word, None) => format!("{:02x}>", word),
             WordGroup::HighByte(word, None) => format!("{:02x}<", word >> 8),
             WordGroup::HighNibble(word, None) => format!("{:01x}<", word >> 12),
-            WordGroup::LowNibble(word, Some(count)) => format!("{:01x}>{}*", word, count),
-            WordGroup::LowByte(word, Some(count)) => format!("{:02x}>{}*", word, count),
-            WordGroup::HighByte(word, Some(count)) => format!("{:02x}<{}*", word >> 8, count),
-            WordGroup::HighNibble(word, Some(count)) => format!("{:01x}<{}*", word >> 12, count),
+            WordGroup::LowNibble(word, Some(count)) => format!("{:01x}>{}", word, chain_notation(*count, '*')),
+            WordGroup::LowByte(word, Some(count)) => format!("{:02x}>{}", word, chain_notation(*count, '*')),
+            WordGroup::HighByte(word, Some(count)) => format!("{:02x}<{}", word >> 8, chain_notation(*count, '*')),
+            WordGroup::HighNibble(word, Some(count)) => format!("{:01x}<{}", word >> 12, chain_notation(*count, '*')),
+        }
+    }
+
+    /// The group spelled out as plain hex words, the only ASCII the loader reads.
+    ///
+    /// Control runes have no ASCII form, so zeros and repeats are written out in
+    /// full, using `last_written` to know what a repeat stands for. Skips can't
+    /// be expressed at all.
+    pub fn ascii(&self, last_written: &mut u16) -> Result<String, String> {
+        let (value, count) = match *self {
+            WordGroup::Skip | WordGroup::SkipChain(_) => {
+                return Err("Skipping over unwritten words can't be spelled out in digits".to_string());
+            }
+            WordGroup::Zero => return Ok("0000".to_string()),
+            WordGroup::ZeroChain(count) => return Ok("0000".repeat(count)),
+            WordGroup::Repeat(count) => (*last_written, count),
+            WordGroup::Word(word) => (word.value(), 1),
+            WordGroup::WordChain(word, count) => (word.value(), count + 1),
+            WordGroup::LowNibble(word, repeat)
+            | WordGroup::LowByte(word, repeat)
+            | WordGroup::HighByte(word, repeat)
+            | WordGroup::HighNibble(word, repeat) => (word, repeat.unwrap_or(0) + 1),
         };
-        write!(f, "{}", string_to_rune(&hex))
+        *last_written = value;
+        Ok(format!("{:04x}", value).repeat(count))
+    }
+
+    /// Number of runes this group encodes to.
+    pub fn rune_count(&self) -> usize {
+        self.notation().chars().count()
+    }
+}
+
+/// Spells out `count` applications of a counted rune such as `z`, `*` or `_`.
+///
+/// A count of four digits would fill the accumulator and be written as a word,
+/// so larger counts are split into several chains of at most `MAX_CHAIN`. A
+/// bare rune already counts as one, so a count of one needs no digits.
+fn chain_notation(count: usize, rune: char) -> String {
+    let mut left = count;
+    let mut out = String::new();
+    while left > 0 {
+        let diff = left.min(MAX_CHAIN);
+        if diff == 1 {
+            out.push(rune);
+        } else {
+            out += &format!("{:x}{}", diff, rune);
+        }
+        left -= diff;
+    }
+    out
+}
+
+#[test]
+fn test_chain_notation() {
+    assert_eq!(chain_notation(0, 'z'), "");
+    assert_eq!(chain_notation(1, 'z'), "z");
+    assert_eq!(chain_notation(0x10, '*'), "10*");
+    assert_eq!(chain_notation(0xfff, '_'), "fff_");
+    assert_eq!(chain_notation(0x1000, 'z'), "fffzz");
+    assert_eq!(chain_notation(0x1001, 'z'), "fffz2z");
+    assert_eq!(chain_notation(0x10000, '*'), "fff*".repeat(0x10) + "10*");
+}
+
+impl Display for WordGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", string_to_rune(&self.notation()).map_err(|_| std::fmt::Error)?)
     }
 }
 
 #[test]
 fn test_word_groups() {
-    assert_eq!(string_to_rune(&WordGroup::Skip.to_string()), "ᚢ");
-    assert_eq!(string_to_rune(&WordGroup::SkipChain(3).to_string()), "ᛃᚢ");
-    assert_eq!(string_to_rune(&WordGroup::Zero.to_string()), "ᚠ");
-    assert_eq!(string_to_rune(&WordGroup::ZeroChain(3).to_string()), "ᛃᚠ");
-    assert_eq!(string_to_rune(&WordGroup::Word(Word::new(0xdead)).to_string()), "ᛜᛞᛖᛜ");
-    assert_eq!(string_to_rune(&WordGroup::HighNibble(0x5000, None).to_string()), "ᛇᚲ");
-    assert_eq!(string_to_rune(&WordGroup::LowNibble(0xf, None).to_string()), "ᛟ×");
-    assert_eq!(string_to_rune(&WordGroup::HighByte(0x3300, None).to_string()), "ᛃᛃᚲ");
-    assert_eq!(string_to_rune(&WordGroup::LowByte(0x42, None).to_string()), "ᛈᛁ×");
-    assert_eq!(string_to_rune(&WordGroup::HighNibble(0x5000, Some(4)).to_string()), "ᛇᚲᛈᚠ");
-    assert_eq!(string_to_rune(&WordGroup::LowNibble(0xf, Some(2)).to_string()), "ᛟ×ᛁᚠ");
-    assert_eq!(string_to_rune(&WordGroup::HighByte(0x3300, Some(6)).to_string()), "ᛃᛃᚲᛉᚠ");
-    assert_eq!(string_to_rune(&WordGroup::LowByte(0x42, Some(5)).to_string()), "ᛈᛁ×ᛇᚠ");
+    assert_eq!(string_to_rune(&WordGroup::Skip.to_string()).unwrap(), "ᚢ");
+    assert_eq!(string_to_rune(&WordGroup::SkipChain(3).to_string()).unwrap(), "ᛃᚢ");
+    assert_eq!(string_to_rune(&WordGroup::Zero.to_string()).unwrap(), "ᚠ");
+    assert_eq!(string_to_rune(&WordGroup::ZeroChain(3).to_string()).unwrap(), "ᛃᚠ");
+    assert_eq!(string_to_rune(&WordGroup::Word(Word::new(0xdead)).to_string()).unwrap(), "ᛜᛞᛖᛜ");
+    assert_eq!(string_to_rune(&WordGroup::HighNibble(0x5000, None).to_string()).unwrap(), "ᛇᚲ");
+    assert_eq!(string_to_rune(&WordGroup::LowNibble(0xf, None).to_string()).unwrap(), "ᛟ×");
+    assert_eq!(string_to_rune(&WordGroup::HighByte(0x3300, None).to_string()).unwrap(), "ᛃᛃᚲ");
+    assert_eq!(string_to_rune(&WordGroup::LowByte(0x42, None).to_string()).unwrap(), "ᛈᛁ×");
+    assert_eq!(string_to_rune(&WordGroup::HighNibble(0x5000, Some(4)).to_string()).unwrap(), "ᛇᚲᛈᚱ");
+    assert_eq!(string_to_rune(&WordGroup::LowNibble(0xf, Some(2)).to_string()).unwrap(), "ᛟ×ᛁᚱ");
+    assert_eq!(string_to_rune(&WordGroup::HighByte(0x3300, Some(6)).to_string()).unwrap(), "ᛃᛃᚲᛉᚱ");
+    assert_eq!(string_to_rune(&WordGroup::LowByte(0x42, Some(5)).to_string()).unwrap(), "ᛈᛁ×ᛇᚱ");
+    assert_eq!(string_to_rune(&WordGroup::ZeroChain(0x1234).to_string()).unwrap(), "ᛟᛟᛟᚠᛁᛃᛇᚠ");
+
+    // A repeated group writes the word once and then `count` more times.
+    let words = MemoryImage::from_words(&[Some(Word::new(0x42)); 6]);
+    assert_eq!(crate::verify::verify(&WordGroup::LowByte(0x42, Some(5)).to_string(), 0, &words), Ok(()));
+}
+
+#[test]
+fn test_word_group_ascii() {
+    let groups = [
+        WordGroup::Word(Word::new(0xdead)),
+        WordGroup::ZeroChain(2),
+        WordGroup::Repeat(2),
+        WordGroup::LowByte(0x42, Some(1)),
+    ];
+    let mut last_written = 0;
+    let ascii: Result<String, String> = groups.iter().map(|g| g.ascii(&mut last_written)).collect();
+    assert_eq!(ascii, Ok("dead00000000deaddead00420042".to_string()));
+    assert!(WordGroup::SkipChain(2).ascii(&mut last_written).is_err());
 }
 
 #[test]
@@ -240,14 +246,14 @@ impl WordReader {
         let word = &self.words[self.index];
         self.index += 1;
 
-        return Some(*word);
+        Some(*word)
     }
 
     pub fn advance(&mut self, count: usize) {
         self.index += count;
     }
 
-    #[allow(unused)]
+    #[cfg(test)]
     pub fn peek(&self) -> Option<Word> {
         if self.index >= self.words.len() {
             return None;
@@ -275,52 +281,200 @@ impl WordReader {
 
 pub struct WordGroupConstructor {
     reader: WordReader,
+    written: Vec<bool>,
     groups: Vec<WordGroup>,
     pub word_count: u16,
 }
 
 impl WordGroupConstructor {
+    #[cfg(test)]
     pub fn new(words: Vec<Word>) -> Self {
-        WordGroupConstructor { reader: WordReader::new(words.clone()), groups: Vec::new(), word_count: 0 }
+        let written = vec![true; words.len()];
+        WordGroupConstructor { reader: WordReader::new(words), written, groups: Vec::new(), word_count: 0 }
+    }
+
+    /// Builds a constructor for an image, where unwritten words must be left
+    /// untouched rather than written.
+    pub fn from_image(image: &MemoryImage) -> Self {
+        let words = image.to_words();
+        let written = words.iter().map(Option::is_some).collect();
+        let words = words.into_iter().map(|w| w.unwrap_or(Word::new(0))).collect();
+        WordGroupConstructor { reader: WordReader::new(words), written, groups: Vec::new(), word_count: 0 }
+    }
+
+    /// Number of consecutive positions from `index` whose written state is `written`.
+    fn count_written(&self, index: usize, written: bool) -> usize {
+        self.written[index..].iter().take_while(|&&w| w == written).count()
+    }
+
+    fn skip(count: usize) -> WordGroup {
+        if count == 1 { WordGroup::Skip } else { WordGroup::SkipChain(count) }
     }
 
     pub fn construct(&mut self) -> Option<Vec<WordGroup>> {
         while let Some(word) = self.reader.next() {
-            let repeats = self.reader.count_ahead(|w| w == word);
+            let index = self.reader.index - 1;
+            if !self.written[index] {
+                let gap = self.count_written(index, false);
+                // Nothing to write past a trailing gap, so don't skip over it.
+                if index + gap < self.written.len() {
+                    self.groups.push(Self::skip(gap));
+                }
+                self.reader.advance(gap - 1);
+                continue;
+            }
+
+            let written = self.count_written(index + 1, true);
+            let repeats = self.reader.count_ahead(|w| w == word).min(written);
             let repeat = if repeats > 0 { Some(repeats) } else { None };
-            let w = word.value();
-            let a = if (w & 0xf000) == w { 0b1000 } else { 0 };
-            let b = if (w & 0x0f00) == w { 0b0100 } else { 0 };
-            let c = if (w & 0x00f0) == w { 0b0010 } else { 0 };
-            let d = if (w & 0x000f) == w { 0b0001 } else { 0 };
-            let bits = a | b | c | d;
-            self.groups.push(match bits {
-                0b1000 => WordGroup::HighNibble(word.value() as u16, repeat),
-                0b1100 => WordGroup::HighByte(word.value() as u16, repeat),
-                0b0011 => WordGroup::LowByte(word.value() as u16, repeat),
-                0b0001 => WordGroup::LowNibble(word.value() as u16, repeat),
-                _ => {
-                    match w {
-                        0 => match repeats {
-                            0 => WordGroup::Zero,
-                            _ => WordGroup::ZeroChain(repeats + 1),
-                        },
-                        _ => match repeats {
-                            0 => WordGroup::Word(word),
-                            _ => WordGroup::WordChain(word, repeats),
+            self.groups.push(WordGroup::literal(word, repeat));
+            self.reader.advance(repeats);
+        }
+
+        self.word_count = self.reader.index as u16;
+
+        Some(self.groups.clone())
+    }
+
+    /// Finds the shortest rune encoding of the remaining words.
+    ///
+    /// Every nonzero word is either written literally or repeated from the
+    /// previous nonzero word, so the value `ᚱ` repeats at any position is fixed
+    /// by the input. That leaves a plain shortest-path search over word
+    /// positions, where each step is a literal write, a zero run or a repeat run.
+    pub fn construct_optimal(&mut self) -> Option<Vec<WordGroup>> {
+        let start = self.reader.index;
+        let words: Vec<Option<Word>> = self.reader.words[start..].iter()
+            .zip(&self.written[start..])
+            .map(|(&word, &written)| if written { Some(word) } else { None })
+            .collect();
+        // Words after the last written one are left alone entirely.
+        let len = words.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+
+        // Longest run of identical words, or of unwritten words, starting at each position.
+        let mut runs = vec![0; len + 1];
+        for i in (0..len).rev() {
+            runs[i] = if i + 1 < len && words[i + 1] == words[i] { runs[i + 1] + 1 } else { 1 };
+        }
+
+        // Fewest runes first, then fewest groups to keep the output readable.
+        let mut cost = vec![(usize::MAX, usize::MAX); len + 1];
+        let mut step: Vec<Option<(usize, WordGroup)>> = vec![None; len + 1];
+        let mut last_written = 0;
+        cost[0] = (0, 0);
+
+        for i in 0..len {
+            let base = cost[i];
+            let mut relax = |count: usize, group: WordGroup| {
+                let total = (base.0 + group.rune_count(), base.1 + 1);
+                if total <= cost[i + count] {
+                    cost[i + count] = total;
+                    step[i + count] = Some((i, group));
+                }
+            };
+
+            // A chain costs the same for every count with the same number of
+            // digits, so only the longest count of each width is worth trying.
+            let counts = [1, 0xf, 0xff, MAX_CHAIN].map(|max| max.min(runs[i]));
+
+            match words[i] {
+                None => {
+                    for count in counts {
+                        relax(count, Self::skip(count));
+                    }
+                }
+                Some(word) if word.value() == 0 => {
+                    for count in counts {
+                        relax(count, if count == 1 { WordGroup::Zero } else { WordGroup::ZeroChain(count) });
+                    }
+                }
+                Some(word) => {
+                    relax(1, WordGroup::literal(word, None));
+                    if word.value() == last_written {
+                        for count in counts {
+                            relax(count, WordGroup::Repeat(count));
                         }
                     }
-                },
-            });
-            self.reader.advance(repeats);
+                    last_written = word.value();
+                }
+            }
+        }
+
+        let mut groups = Vec::new();
+        let mut i = len;
+        while let Some((prev, group)) = step[i] {
+            groups.push(group);
+            i = prev;
         }
+        groups.reverse();
 
+        // Fold repeats into the literal they follow, matching `construct`.
+        for group in groups {
+            match (self.groups.last_mut(), group) {
+                (Some(WordGroup::Word(word)), WordGroup::Repeat(count)) => {
+                    *self.groups.last_mut().unwrap() = WordGroup::WordChain(*word, count);
+                }
+                (Some(WordGroup::LowNibble(_, repeat @ None)), WordGroup::Repeat(count))
+                | (Some(WordGroup::LowByte(_, repeat @ None)), WordGroup::Repeat(count))
+                | (Some(WordGroup::HighByte(_, repeat @ None)), WordGroup::Repeat(count))
+                | (Some(WordGroup::HighNibble(_, repeat @ None)), WordGroup::Repeat(count)) => {
+                    *repeat = Some(count);
+                }
+                (_, group) => self.groups.push(group),
+            }
+        }
+
+        self.reader.advance(words.len());
         self.word_count = self.reader.index as u16;
 
         Some(self.groups.clone())
     }
 }
 
+/// Longest count a chain can carry before its digits fill a whole word.
+const MAX_CHAIN: usize = 0xfff;
+
+#[test]
+fn test_construct_optimal() {
+    let encode = |words: Vec<u16>| {
+        let groups = WordGroupConstructor::new(words.into_iter().map(Word::new).collect()).construct_optimal().unwrap();
+        groups.iter().map(|g| g.notation()).collect::<String>()
+    };
+
+    assert_eq!(encode(vec![]), "");
+    assert_eq!(encode(vec![0, 0, 0, 0]), "4z");
+    assert_eq!(encode(vec![0xffff, 0xffff, 0xffff, 0xffff]), "ffff3*");
+    assert_eq!(encode(vec![0x3000, 0x0110, 0x8006, 0xf]), "3<01108006f>");
+    // A repeat can pick up the last written word after a run of zeros.
+    assert_eq!(encode(vec![0xdead, 0, 0xdead, 0xdead]), "deadz2*");
+    assert_eq!(encode(vec![0x42, 0, 0, 0x42]), "42>2z*");
+    // Long runs are split into chains the decoder can count.
+    assert_eq!(encode(vec![0; 0x1001]), "fffz2z");
+}
+
+#[test]
+fn test_construct_sparse() {
+    let words = |words: Vec<Option<u16>>| MemoryImage::from_words(&words.into_iter().map(|w| w.map(Word::new)).collect::<Vec<_>>());
+    let notation = |groups: Vec<WordGroup>| groups.iter().map(|g| g.notation()).collect::<String>();
+
+    // Words after the last written one aren't part of the image.
+    let input = words(vec![Some(0xdead), None, None, Some(0), Some(0xdead), None]);
+    let mut ctor = WordGroupConstructor::from_image(&input);
+    assert_eq!(notation(ctor.construct().unwrap()), "dead2_zdead");
+    assert_eq!(ctor.word_count, 5);
+    let mut ctor = WordGroupConstructor::from_image(&input);
+    assert_eq!(notation(ctor.construct_optimal().unwrap()), "dead2_z*");
+    assert_eq!(ctor.word_count, 5);
+
+    // Repeats stop at the edge of a gap.
+    let input = words(vec![Some(0x1234), Some(0x1234), None, Some(0x1234)]);
+    assert_eq!(notation(WordGroupConstructor::from_image(&input).construct().unwrap()), "1234*_1234");
+    assert_eq!(notation(WordGroupConstructor::from_image(&input).construct_optimal().unwrap()), "1234*_*");
+
+    let input = words(vec![None; 0x20]);
+    assert_eq!(notation(WordGroupConstructor::from_image(&input).construct_optimal().unwrap()), "");
+}
+
 
 #[test]
 fn test_vector() {
@@ -332,32 +486,35 @@ fn test_vector() {
 fn test_runes() {
     let rune = char_to_rune('a');
     assert_eq!(rune, Some('ᛖ'));
-    let runes = "This is a test. 1234567890";
-    let output = string_to_rune(runes);
-    assert_eq!(output, "ᛖᛞᚾᛁᛃᛈᛇᛉᛊᛏᛒᚺ");
+    assert_eq!(string_to_rune("ae1234567890ᚢ"), Ok("ᛖᛞᚾᛁᛃᛈᛇᛉᛊᛏᛒᚺᚢ".to_string()));
+    assert_eq!(string_to_rune("This is a test. 1234567890"), Err("`T` is not hex notation or a rune.".to_string()));
 }
 
 #[test]
 fn test_alignments() {
     let input: Vec<Word> = vec![0x3000,0x0110,0x8006,0xf].into_iter().map(Word::new).collect();
-    let output: String = format!("{:04x}", input[0].value());
+    let output: String = WordGroupConstructor::new(input.clone()).construct().unwrap().iter().map(|g| g.to_string()).collect();
     assert_eq!(output, "ᛃᚲᚺᚾᚾᚺᛏᚺᚺᛉᛟ×");
+    assert_eq!(crate::verify::verify(&output, 0, &MemoryImage::from_words(&input.into_iter().map(Some).collect::<Vec<_>>())), Ok(()));
 }
 
 #[test]
 fn test_zeroes() {
     let input: Vec<Word> = vec![0,0,0,0].into_iter().map(Word::new).collect();
-    let output: String = format!("{:04x}", input[0].value());
+    let output: String = WordGroupConstructor::new(input.clone()).construct().unwrap().iter().map(|g| g.to_string()).collect();
     assert_eq!(output, "ᛈᚠ");
+    assert_eq!(crate::verify::verify(&output, 0, &MemoryImage::from_words(&input.into_iter().map(Some).collect::<Vec<_>>())), Ok(()));
 }
 
 #[test]
 fn test_repeats() {
     let input: Vec<Word> = vec![0xffff,0xffff,0xffff,0xffff].into_iter().map(Word::new).collect();
-    let output: String = format!("{:04x}", input[0].value());
+    let output: String = WordGroupConstructor::new(input.clone()).construct().unwrap().iter().map(|g| g.to_string()).collect();
     assert_eq!(output, "ᛟᛟᛟᛟᛃᚱ");
+    assert_eq!(crate::verify::verify(&output, 0, &MemoryImage::from_words(&input.into_iter().map(Some).collect::<Vec<_>>())), Ok(()));
 
     let input: Vec<Word> = vec![0x348c,0x348c,0x348c,0x348c].into_iter().map(Word::new).collect();
-    let output: String = format!("{:04x}", input[0].value());
+    let output: String = WordGroupConstructor::new(input.clone()).construct().unwrap().iter().map(|g| g.to_string()).collect();
     assert_eq!(output, "ᛃᛈᛏᛚᛃᚱ");
+    assert_eq!(crate::verify::verify(&output, 0, &MemoryImage::from_words(&input.into_iter().map(Some).collect::<Vec<_>>())), Ok(()));
 }
@@ -0,0 +1,95 @@
+use crate::{image::{MemoryImage, MEMORY_SIZE}, reverse::reverse_write};
+
+/// Decodes `runes` and checks that every written word of `expected` landed
+/// `offset` words further on with the expected value, and that nothing else
+/// was written.
+///
+/// On mismatch the error lists each differing address alongside the expected
+/// and decoded values.
+pub fn verify(runes: &str, offset: u16, expected: &MemoryImage) -> Result<(), String> {
+    let decoded = reverse_write(runes.to_string());
+    let mut errors = Vec::new();
+
+    for addr in 0..MEMORY_SIZE as u16 {
+        match (expected.get(addr.wrapping_sub(offset)), decoded.get(addr)) {
+            (Some(expected), Some(actual)) if actual == expected => {}
+            (Some(expected), Some(actual)) => errors.push(format!("{:04x}: expected {}, decoded {}", addr, expected, actual)),
+            (Some(expected), None) => errors.push(format!("{:04x}: expected {}, nothing written", addr, expected)),
+            (None, Some(actual)) => errors.push(format!("{:04x}: expected nothing, decoded {}", addr, actual)),
+            (None, None) => {}
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} word(s) differ after decoding:\n{}", errors.len(), errors.join("\n")))
+    }
+}
+
+#[test]
+fn test_verify() {
+    use crate::word::Word;
+
+    let words = MemoryImage::from_words(&[Some(Word::new(0xdead)), None, Some(Word::new(0x0042))]);
+    assert_eq!(verify("ᛜᛞᛖᛜᚢᛈᛁ×", 0, &words), Ok(()));
+    assert_eq!(verify("ᚾᚢᛜᛞᛖᛜᚢᛈᛁ×", 1, &words), Ok(()));
+    assert_eq!(
+        verify("ᛜᛞᛖᛜᚢᛈᛁᚲ", 0, &words),
+        Err("1 word(s) differ after decoding:\n0002: expected 0042, decoded 4200".to_string())
+    );
+    assert_eq!(
+        verify("ᛜᛞᛖᛜ", 0, &words),
+        Err("1 word(s) differ after decoding:\n0002: expected 0042, nothing written".to_string())
+    );
+    assert_eq!(
+        verify("ᛜᛞᛖᛜᚠᛈᛁ×ᛁᚱ", 0, &words),
+        Err("3 word(s) differ after decoding:\n0001: expected nothing, decoded 0000\n0003: expected nothing, decoded 0042\n0004: expected nothing, decoded 0042".to_string())
+    );
+}
+
+#[test]
+fn test_verify_constructors() {
+    use crate::{tokens::WordGroupConstructor, word::Word};
+
+    // Deterministic mix of literals, aligned values, zero runs, repeats and gaps.
+    let mut seed = 0x1234u32;
+    let mut words = Vec::new();
+    for _ in 0..0x400 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let run = (seed >> 8) as usize % 6 + 1;
+        let word = match (seed >> 16) % 7 {
+            0 => None,
+            1 => Some(0),
+            2 => Some((seed >> 4) as u16 & 0x000f),
+            3 => Some((seed >> 4) as u16 & 0xff00),
+            4 => Some((seed >> 4) as u16 & 0x00ff),
+            _ => Some((seed >> 4) as u16),
+        };
+        words.extend(std::iter::repeat_n(word.map(Word::new), run));
+    }
+
+    let words = MemoryImage::from_words(&words);
+    let greedy: String = WordGroupConstructor::from_image(&words).construct().unwrap().iter().map(|g| g.to_string()).collect();
+    let optimal: String = WordGroupConstructor::from_image(&words).construct_optimal().unwrap().iter().map(|g| g.to_string()).collect();
+    assert_eq!(verify(&greedy, 0, &words), Ok(()));
+    assert_eq!(verify(&optimal, 0, &words), Ok(()));
+    assert!(optimal.chars().count() <= greedy.chars().count());
+}
+
+#[test]
+fn test_verify_long_runs() {
+    use crate::{tokens::WordGroupConstructor, word::Word};
+
+    let cases: Vec<Vec<Option<Word>>> = vec![
+        vec![Some(Word::new(0)); 0x1000],
+        vec![Some(Word::new(0xbeef)); 0x1000],
+        vec![Some(Word::new(0x7)); 0x1000],
+        [vec![None; 0xfff], vec![Some(Word::new(0x1234))]].concat(),
+    ];
+    for words in cases {
+        let words = MemoryImage::from_words(&words);
+        let greedy: String = WordGroupConstructor::from_image(&words).construct().unwrap().iter().map(|g| g.to_string()).collect();
+        assert_eq!(verify(&greedy, 0, &words), Ok(()));
+    }
+}
@@ -52,9 +52,9 @@ impl From<isize> for Word {
     }
 }
 
-impl Into<u16> for Word {
-    fn into(self) -> u16 {
-        self.0
+impl From<Word> for u16 {
+    fn from(word: Word) -> Self {
+        word.0
     }
 }
 